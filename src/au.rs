@@ -1,8 +1,20 @@
+use crate::dpb::{DpbState, DpbViolation};
+use crate::gap::FrameNumGap;
 use crate::nal::{Nal, NalUnitType};
+use crate::poc::PicOrderCnt;
 use crate::pps::Pps;
-use crate::sei::{SeiMessage, SeiPayload};
+use crate::reorder::OutputTiming;
+use crate::sei::{
+    Afd, AtscUserData, BarData, BufferingPeriod, CaptionPacket, CustomSei,
+    DecRefPicMarkingRepetition, DisplayOrientation, EncoderInfo, FilmGrainCharacteristics,
+    FramePackingArrangement, HdrMetadata, KlvLocalSet, PanScanRect, PicTiming, PrecisionTimeStamp,
+    SeiMessage, SeiPayload, ToneMappingInfo,
+};
 use crate::slice::{PictureId, SliceHeader};
 use crate::sps::Sps;
+use crate::timecode::Timecode;
+use crate::{Error, Result};
+use std::any::Any;
 use std::borrow::Cow;
 use std::sync::Arc;
 
@@ -13,6 +25,26 @@ pub enum AccessUnitKind {
     NonIdr,
 }
 
+/// Controls which in-band NAL units [`AccessUnit::to_avcc_bytes`] drops.
+/// MP4 tracks usually carry parameter sets in the avcC record and have no
+/// use for access unit delimiters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AvccWriteOptions {
+    /// Drop SPS, PPS and SPS extension NAL units.
+    pub strip_parameter_sets: bool,
+    pub strip_aud: bool,
+}
+
+impl AvccWriteOptions {
+    fn strips(&self, nal_type: NalUnitType) -> bool {
+        match nal_type {
+            NalUnitType::Sps | NalUnitType::Pps | NalUnitType::SpsExt => self.strip_parameter_sets,
+            NalUnitType::Aud => self.strip_aud,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AccessUnit {
     pub nals: Vec<Nal>,
@@ -21,6 +53,19 @@ pub struct AccessUnit {
     pub sps: Option<Arc<Sps>>,
     pub pps: Option<Arc<Pps>>,
     pub picture_id: Option<PictureId>,
+    pub slice_header: Option<SliceHeader>,
+    pub poc: Option<PicOrderCnt>,
+    pub output_timing: Option<OutputTiming>,
+    pub reference_state: Option<DpbState>,
+    pub dpb_violations: Vec<DpbViolation>,
+    pub frame_num_gap: Option<FrameNumGap>,
+    pub sei_messages: Vec<SeiMessage>,
+    pub timecode: Option<Timecode>,
+    /// HDR static metadata in effect for this picture, including values
+    /// signalled by earlier access units.
+    pub hdr_metadata: HdrMetadata,
+    /// Payloads decoded by handlers registered on the parser.
+    pub custom_sei: Vec<CustomSei>,
 }
 
 impl AccessUnit {
@@ -32,6 +77,16 @@ impl AccessUnit {
             sps: None,
             pps: None,
             picture_id: None,
+            slice_header: None,
+            poc: None,
+            output_timing: None,
+            reference_state: None,
+            dpb_violations: Vec::new(),
+            frame_num_gap: None,
+            sei_messages: Vec::new(),
+            timecode: None,
+            hdr_metadata: HdrMetadata::default(),
+            custom_sei: Vec::new(),
         }
     }
 
@@ -43,6 +98,22 @@ impl AccessUnit {
         self.nals.iter()
     }
 
+    pub fn first_vcl_nal(&self) -> Option<&Nal> {
+        self.nals.iter().find(|nal| nal.is_vcl())
+    }
+
+    pub fn top_field_order_cnt(&self) -> Option<i32> {
+        self.poc.and_then(|poc| poc.top_field_order_cnt)
+    }
+
+    pub fn bottom_field_order_cnt(&self) -> Option<i32> {
+        self.poc.and_then(|poc| poc.bottom_field_order_cnt)
+    }
+
+    pub fn pic_order_cnt(&self) -> Option<i32> {
+        self.poc.map(|poc| poc.pic_order_cnt)
+    }
+
     pub fn to_annexb_bytes(&self) -> Cow<'_, [u8]> {
         let mut bytes = Vec::new();
         
@@ -55,8 +126,7 @@ impl AccessUnit {
             
             bytes.extend_from_slice(start_code);
             
-            let header = ((nal.ref_idc & 0b11) << 5) | (nal.nal_type.as_u8() & 0b11111);
-            bytes.push(header);
+            bytes.push(nal.header_byte());
             
             bytes.extend_from_slice(&nal.ebsp);
         }
@@ -64,6 +134,36 @@ impl AccessUnit {
         Cow::Owned(bytes)
     }
 
+    /// Serializes the access unit as an ISO/IEC 14496-15 sample, each NAL
+    /// unit prefixed with its size in `length_size` (1, 2 or 4) bytes.
+    pub fn to_avcc_bytes(&self, length_size: u8, options: AvccWriteOptions) -> Result<Vec<u8>> {
+        if !matches!(length_size, 1 | 2 | 4) {
+            return Err(Error::BitstreamError(format!(
+                "Invalid NAL length size {}",
+                length_size
+            )));
+        }
+        
+        let max_len = (1u64 << (8 * length_size as u32)) - 1;
+        let mut bytes = Vec::new();
+        
+        for nal in self.nals.iter().filter(|nal| !options.strips(nal.nal_type)) {
+            let len = 1 + nal.ebsp.len() as u64;
+            if len > max_len {
+                return Err(Error::BitstreamError(format!(
+                    "NAL unit of {} bytes does not fit a {}-byte length",
+                    len, length_size
+                )));
+            }
+            
+            bytes.extend_from_slice(&len.to_be_bytes()[8 - length_size as usize..]);
+            bytes.push(nal.header_byte());
+            bytes.extend_from_slice(&nal.ebsp);
+        }
+        
+        Ok(bytes)
+    }
+
     pub fn add_nal(&mut self, nal: Nal) {
         if nal.nal_type == NalUnitType::IdrSlice {
             self.kind = AccessUnitKind::Idr;
@@ -81,26 +181,181 @@ impl AccessUnit {
         self.pps = Some(pps);
     }
 
+    /// Parses every SEI NAL unit against the attached SPS into
+    /// `sei_messages`. SEI NAL units that fail to parse are skipped.
+    pub fn parse_sei_messages(&mut self) {
+        let sps = self.sps.as_deref();
+        self.sei_messages = self
+            .nals
+            .iter()
+            .filter(|nal| nal.nal_type == NalUnitType::Sei)
+            .filter_map(|nal| SeiMessage::parse_with_sps(&nal.to_rbsp(), sps).ok())
+            .flatten()
+            .collect();
+    }
+
+    /// Inserts an SEI NAL unit carrying `messages` after any AUD and
+    /// parameter sets and before the first VCL NAL unit (7.4.1.2.3), then
+    /// refreshes `sei_messages` and the recovery point `kind`. Fields the
+    /// parser derives from earlier access units (`timecode`,
+    /// `hdr_metadata`, `custom_sei`) are left as they are.
+    pub fn insert_sei(&mut self, messages: &[SeiMessage]) -> Result<()> {
+        let mut nal = SeiMessage::to_nal(messages)?;
+
+        let mut pos = self
+            .nals
+            .iter()
+            .position(|nal| nal.is_vcl())
+            .unwrap_or(self.nals.len());
+        // A prefix NAL unit must stay directly in front of its slice
+        if pos > 0 && self.nals[pos - 1].nal_type == NalUnitType::Prefix {
+            pos -= 1;
+        }
+        if pos == 0 {
+            nal.start_code_len = 4;
+        }
+
+        self.nals.insert(pos, nal);
+        self.parse_sei_messages();
+        self.check_recovery_point();
+        Ok(())
+    }
+
     pub fn check_recovery_point(&mut self) {
-        for nal in &self.nals {
-            if nal.nal_type == NalUnitType::Sei {
-                let rbsp = nal.to_rbsp();
-                if let Ok(messages) = SeiMessage::parse(&rbsp) {
-                    for msg in messages {
-                        if let SeiPayload::RecoveryPoint { recovery_frame_cnt, .. } = msg.payload {
-                            if recovery_frame_cnt == 0 {
-                                self.kind = AccessUnitKind::RecoveryPoint(0);
-                                self.is_keyframe = true;
-                            } else {
-                                self.kind = AccessUnitKind::RecoveryPoint(recovery_frame_cnt);
-                            }
-                        }
-                    }
+        for msg in &self.sei_messages {
+            if let SeiPayload::RecoveryPoint { recovery_frame_cnt, .. } = msg.payload {
+                if recovery_frame_cnt == 0 {
+                    self.kind = AccessUnitKind::RecoveryPoint(0);
+                    self.is_keyframe = true;
+                } else {
+                    self.kind = AccessUnitKind::RecoveryPoint(recovery_frame_cnt);
                 }
             }
         }
     }
 
+    pub fn buffering_period(&self) -> Option<&BufferingPeriod> {
+        self.sei_messages.iter().find_map(|msg| match &msg.payload {
+            SeiPayload::BufferingPeriod(bp) => Some(bp),
+            _ => None,
+        })
+    }
+
+    pub fn pic_timing(&self) -> Option<&PicTiming> {
+        self.sei_messages.iter().find_map(|msg| match &msg.payload {
+            SeiPayload::PicTiming(timing) => Some(timing),
+            _ => None,
+        })
+    }
+
+    /// ATSC A/53 caption packets carried by this picture, in bitstream
+    /// order. Access units from `next_output_access_unit` deliver them in
+    /// presentation order, which is what caption decoders expect.
+    pub fn captions(&self) -> impl Iterator<Item = &CaptionPacket> {
+        self.atsc_user_data()
+            .filter_map(|data| match data {
+                AtscUserData::CcData(cc_data) => Some(&cc_data.packets),
+                _ => None,
+            })
+            .flatten()
+    }
+
+    pub fn afd(&self) -> Option<&Afd> {
+        self.atsc_user_data().find_map(|data| match data {
+            AtscUserData::Afd(afd) => Some(afd),
+            _ => None,
+        })
+    }
+
+    pub fn bar_data(&self) -> Option<&BarData> {
+        self.atsc_user_data().find_map(|data| match data {
+            AtscUserData::BarData(bar_data) => Some(bar_data),
+            _ => None,
+        })
+    }
+
+    pub fn frame_packing_arrangement(&self) -> Option<&FramePackingArrangement> {
+        self.sei_messages.iter().find_map(|msg| match &msg.payload {
+            SeiPayload::FramePackingArrangement(fpa) => Some(fpa),
+            _ => None,
+        })
+    }
+
+    pub fn display_orientation(&self) -> Option<&DisplayOrientation> {
+        self.sei_messages.iter().find_map(|msg| match &msg.payload {
+            SeiPayload::DisplayOrientation(orientation) => Some(orientation),
+            _ => None,
+        })
+    }
+
+    pub fn dec_ref_pic_marking_repetition(&self) -> Option<&DecRefPicMarkingRepetition> {
+        self.sei_messages.iter().find_map(|msg| match &msg.payload {
+            SeiPayload::DecRefPicMarkingRepetition(repetition) => Some(repetition),
+            _ => None,
+        })
+    }
+
+    pub fn pan_scan_rect(&self) -> Option<&PanScanRect> {
+        self.sei_messages.iter().find_map(|msg| match &msg.payload {
+            SeiPayload::PanScanRect(rect) => Some(rect),
+            _ => None,
+        })
+    }
+
+    /// An access unit may carry one tone mapping message per tone_map_id.
+    pub fn tone_mapping_infos(&self) -> impl Iterator<Item = &ToneMappingInfo> {
+        self.sei_messages.iter().filter_map(|msg| match &msg.payload {
+            SeiPayload::ToneMappingInfo(info) => Some(info),
+            _ => None,
+        })
+    }
+
+    /// Values of type `T` produced by registered SEI handlers.
+    pub fn custom_sei_values<T: Any>(&self) -> impl Iterator<Item = &T> {
+        self.custom_sei.iter().filter_map(|sei| sei.downcast_ref())
+    }
+
+    /// Encoder settings from an x264 info SEI, usually only present in the
+    /// first access unit.
+    pub fn encoder_info(&self) -> Option<EncoderInfo> {
+        self.user_data_unregistered()
+            .find_map(EncoderInfo::from_user_data_unregistered)
+    }
+
+    /// MISB ST 0604 precision time stamp of this picture.
+    pub fn precision_time_stamp(&self) -> Option<PrecisionTimeStamp> {
+        self.user_data_unregistered()
+            .find_map(PrecisionTimeStamp::from_user_data_unregistered)
+    }
+
+    /// KLV local sets, such as MISB ST 0601 metadata, carried in this
+    /// access unit's SEI.
+    pub fn klv_local_sets(&self) -> impl Iterator<Item = KlvLocalSet> + '_ {
+        self.user_data_unregistered()
+            .filter_map(KlvLocalSet::from_user_data_unregistered)
+    }
+
+    fn user_data_unregistered(&self) -> impl Iterator<Item = &[u8]> {
+        self.sei_messages.iter().filter_map(|msg| match &msg.payload {
+            SeiPayload::UserDataUnregistered(payload) => Some(payload.as_slice()),
+            _ => None,
+        })
+    }
+
+    pub fn film_grain_characteristics(&self) -> Option<&FilmGrainCharacteristics> {
+        self.sei_messages.iter().find_map(|msg| match &msg.payload {
+            SeiPayload::FilmGrainCharacteristics(fgc) => Some(fgc),
+            _ => None,
+        })
+    }
+
+    fn atsc_user_data(&self) -> impl Iterator<Item = &AtscUserData> {
+        self.sei_messages.iter().filter_map(|msg| match &msg.payload {
+            SeiPayload::UserDataRegistered(user_data) => user_data.atsc.as_ref(),
+            _ => None,
+        })
+    }
+
     pub fn set_picture_id_from_slice(
         &mut self,
         slice_header: &SliceHeader,
@@ -111,6 +366,12 @@ impl AccessUnit {
     }
 }
 
+impl Default for AccessUnit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct AccessUnitBuilder {
     current_au: Option<AccessUnit>,
     current_picture_id: Option<PictureId>,
@@ -130,18 +391,24 @@ impl AccessUnitBuilder {
         slice_header: Option<&SliceHeader>,
         sps: Option<&Sps>,
     ) -> bool {
-        if nal.nal_type == NalUnitType::Aud {
-            return true;
+        // Non-VCL NAL units that may only precede the first VCL NAL unit of
+        // an access unit (7.4.1.2.3)
+        match nal.nal_type {
+            NalUnitType::Aud => return true,
+            NalUnitType::Sei
+            | NalUnitType::Sps
+            | NalUnitType::Pps
+            | NalUnitType::Prefix
+            | NalUnitType::SubsetSps
+            | NalUnitType::DepthParameterSet
+            | NalUnitType::Reserved(17..=18) => return self.current_picture_id.is_some(),
+            _ => {}
         }
 
-        if !nal.is_vcl() {
+        if !nal.is_vcl() || self.current_picture_id.is_none() {
             return false;
         }
 
-        if self.current_picture_id.is_none() {
-            return true;
-        }
-
         if let (Some(header), Some(sps)) = (slice_header, sps) {
             let new_picture_id = PictureId::from_slice_header(header, nal.nal_type, sps);
             
@@ -170,6 +437,7 @@ impl AccessUnitBuilder {
 
         if is_boundary && self.current_au.is_some() {
             if let Some(mut au) = self.current_au.take() {
+                au.parse_sei_messages();
                 au.check_recovery_point();
                 completed_au = Some(au);
             }
@@ -193,6 +461,10 @@ impl AccessUnitBuilder {
                 let picture_id = PictureId::from_slice_header(&header, nal.nal_type, sps_ref);
                 self.current_picture_id = Some(picture_id.clone());
                 au.picture_id = Some(picture_id);
+                
+                if au.slice_header.is_none() {
+                    au.slice_header = Some(header);
+                }
             }
 
             au.add_nal(nal);
@@ -203,6 +475,7 @@ impl AccessUnitBuilder {
 
     pub fn flush(mut self) -> Option<AccessUnit> {
         if let Some(mut au) = self.current_au.take() {
+            au.parse_sei_messages();
             au.check_recovery_point();
             Some(au)
         } else {
@@ -212,6 +485,7 @@ impl AccessUnitBuilder {
     
     pub fn flush_pending(&mut self) -> Option<AccessUnit> {
         if let Some(mut au) = self.current_au.take() {
+            au.parse_sei_messages();
             au.check_recovery_point();
             self.current_picture_id = None;
             Some(au)
@@ -221,6 +495,12 @@ impl AccessUnitBuilder {
     }
 }
 
+impl Default for AccessUnitBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -234,7 +514,7 @@ mod tests {
             start_code_len: 4,
             ref_idc: 3,
             nal_type: NalUnitType::IdrSlice,
-            ebsp: &[],
+            ebsp: vec![],
         };
         
         au.add_nal(idr_nal);
@@ -250,7 +530,7 @@ mod tests {
             start_code_len: 3,
             ref_idc: 2,
             nal_type: NalUnitType::Sps,
-            ebsp: &[0x42, 0x00, 0x1f],
+            ebsp: vec![0x42, 0x00, 0x1f],
         };
         
         au.add_nal(nal);
@@ -260,4 +540,108 @@ mod tests {
         assert_eq!(bytes[3], 0x47);
         assert_eq!(&bytes[4..], &[0x42, 0x00, 0x1f]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_to_avcc_bytes() {
+        let mut au = AccessUnit::new();
+        for (nal_type, ebsp) in [
+            (NalUnitType::Aud, vec![0xf0]),
+            (NalUnitType::Sps, vec![0x42, 0x00, 0x1f]),
+            (NalUnitType::IdrSlice, vec![0x88; 300]),
+        ] {
+            au.add_nal(Nal { start_code_len: 4, ref_idc: 3, nal_type, ebsp });
+        }
+        
+        let bytes = au.to_avcc_bytes(4, AvccWriteOptions::default()).unwrap();
+        assert_eq!(&bytes[..6], &[0x00, 0x00, 0x00, 0x02, 0x69, 0xf0]);
+        assert_eq!(bytes.len(), 3 * 4 + 2 + 4 + 301);
+        
+        let options = AvccWriteOptions { strip_parameter_sets: true, strip_aud: true };
+        let bytes = au.to_avcc_bytes(2, options).unwrap();
+        assert_eq!(&bytes[..3], &[0x01, 0x2d, 0x65]);
+        assert_eq!(bytes.len(), 2 + 301);
+        
+        // 301 bytes cannot be described by a 1-byte length
+        assert!(au.to_avcc_bytes(1, options).is_err());
+        assert!(au.to_avcc_bytes(3, options).is_err());
+    }
+
+    #[test]
+    fn test_insert_sei() {
+        let nal = |nal_type, ebsp: &[u8]| Nal {
+            start_code_len: 4,
+            ref_idc: 0,
+            nal_type,
+            ebsp: ebsp.to_vec(),
+        };
+
+        let mut au = AccessUnit::new();
+        au.nals = vec![
+            nal(NalUnitType::Aud, &[0xf0]),
+            nal(NalUnitType::Sps, &[0x42]),
+            nal(NalUnitType::Pps, &[0xce]),
+            nal(NalUnitType::IdrSlice, &[0x88]),
+            nal(NalUnitType::IdrSlice, &[0x88]),
+        ];
+
+        let message = SeiMessage::user_data_unregistered([0x22; 16], b"asset-42");
+        au.insert_sei(&[message]).unwrap();
+
+        let types: Vec<_> = au.nals.iter().map(|nal| nal.nal_type).collect();
+        assert_eq!(
+            types,
+            [
+                NalUnitType::Aud,
+                NalUnitType::Sps,
+                NalUnitType::Pps,
+                NalUnitType::Sei,
+                NalUnitType::IdrSlice,
+                NalUnitType::IdrSlice,
+            ]
+        );
+        assert_eq!(au.sei_messages.len(), 1);
+        assert!(au
+            .to_annexb_bytes()
+            .windows(11)
+            .any(|w| w == b"\x00\x00\x01\x06\x05\x18\x22\x22\x22\x22\x22"));
+
+        let mut empty = AccessUnit::new();
+        empty.insert_sei(&[SeiMessage::user_data_unregistered([0; 16], &[])]).unwrap();
+        assert_eq!(empty.nals[0].start_code_len, 4);
+
+        let recovery_point = SeiMessage {
+            payload_type: 6,
+            payload_size: 1,
+            payload: SeiPayload::RecoveryPoint {
+                recovery_frame_cnt: 4,
+                exact_match_flag: true,
+                broken_link_flag: false,
+                changing_slice_group_idc: 0,
+            },
+            raw_payload: None,
+        };
+        empty.insert_sei(&[recovery_point]).unwrap();
+        assert_eq!(empty.nals.len(), 2);
+        assert_eq!(empty.nals[1].ebsp, [0x06, 0x02, 0x2c, 0x40, 0x80]);
+        assert_eq!(empty.kind, AccessUnitKind::RecoveryPoint(4));
+        assert!(matches!(
+            empty.sei_messages[1].payload,
+            SeiPayload::RecoveryPoint { recovery_frame_cnt: 4, exact_match_flag: true, .. }
+        ));
+
+        // Payloads without a writer are copied from the bytes they were read from
+        let sps = crate::sps::test_sps();
+        let mut timed = AccessUnit::new();
+        timed.sps = Some(Arc::new(sps));
+        timed.add_nal(Nal::parse(4, &[0x06, 0x01, 0x01, 0x30, 0x80]).unwrap());
+        timed.parse_sei_messages();
+        let messages = timed.sei_messages.clone();
+        assert!(matches!(messages[0].payload, SeiPayload::PicTiming(_)));
+        timed.insert_sei(&messages).unwrap();
+        assert_eq!(timed.nals[0].ebsp, timed.nals[1].ebsp);
+
+        let unwritable = SeiMessage { raw_payload: None, ..messages[0].clone() };
+        assert!(timed.insert_sei(&[unwritable]).is_err());
+        assert_eq!(timed.nals.len(), 2);
+    }
+}
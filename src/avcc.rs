@@ -0,0 +1,436 @@
+use crate::au::AccessUnit;
+use crate::nal::{rbsp_to_ebsp, NalUnitType};
+use crate::pps::Pps;
+use crate::sps::Sps;
+use crate::{Error, Result};
+use std::sync::Arc;
+
+/// Every profile except Baseline, Main and Extended carries the chroma/bit
+/// depth extension in its record.
+fn has_high_profile_extension(profile_idc: u8) -> bool {
+    !matches!(profile_idc, 66 | 77 | 88)
+}
+
+/// AVCDecoderConfigurationRecord (ISO/IEC 14496-15 5.3.3.1), the payload of
+/// an MP4 `avcC` box or a Matroska `CodecPrivate` for H.264 tracks. Parameter
+/// sets are kept as complete NAL units, header byte included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvcDecoderConfigurationRecord {
+    pub configuration_version: u8,
+    pub profile_indication: u8,
+    pub profile_compatibility: u8,
+    pub level_indication: u8,
+    pub length_size_minus_one: u8,
+    pub sequence_parameter_sets: Vec<Vec<u8>>,
+    pub picture_parameter_sets: Vec<Vec<u8>>,
+    pub high_profile_extension: Option<HighProfileExtension>,
+}
+
+/// Trailing fields present for every profile except Baseline (66), Main (77)
+/// and Extended (88). Some muxers omit them, in which case the record parses
+/// with `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HighProfileExtension {
+    pub chroma_format: u8,
+    pub bit_depth_luma_minus8: u8,
+    pub bit_depth_chroma_minus8: u8,
+    pub sequence_parameter_set_ext: Vec<Vec<u8>>,
+}
+
+impl AvcDecoderConfigurationRecord {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = RecordReader { data, pos: 0 };
+
+        let configuration_version = reader.u8()?;
+        if configuration_version != 1 {
+            return Err(Error::MalformedAvcc(format!(
+                "Unsupported configurationVersion {}",
+                configuration_version
+            )));
+        }
+
+        let profile_indication = reader.u8()?;
+        let profile_compatibility = reader.u8()?;
+        let level_indication = reader.u8()?;
+        let length_size_minus_one = reader.u8()? & 0x03;
+        if length_size_minus_one == 2 {
+            return Err(Error::MalformedAvcc("NAL length size of 3 bytes".into()));
+        }
+
+        let num_sps = reader.u8()? & 0x1f;
+        let sequence_parameter_sets = reader.parameter_sets(num_sps as usize)?;
+
+        let num_pps = reader.u8()?;
+        let picture_parameter_sets = reader.parameter_sets(num_pps as usize)?;
+
+        let high_profile_extension = if has_high_profile_extension(profile_indication)
+            && !reader.is_empty()
+        {
+            let chroma_format = reader.u8()? & 0x03;
+            let bit_depth_luma_minus8 = reader.u8()? & 0x07;
+            let bit_depth_chroma_minus8 = reader.u8()? & 0x07;
+            let num_sps_ext = reader.u8()?;
+            Some(HighProfileExtension {
+                chroma_format,
+                bit_depth_luma_minus8,
+                bit_depth_chroma_minus8,
+                sequence_parameter_set_ext: reader.parameter_sets(num_sps_ext as usize)?,
+            })
+        } else {
+            None
+        };
+
+        Ok(AvcDecoderConfigurationRecord {
+            configuration_version,
+            profile_indication,
+            profile_compatibility,
+            level_indication,
+            length_size_minus_one,
+            sequence_parameter_sets,
+            picture_parameter_sets,
+            high_profile_extension,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![
+            self.configuration_version,
+            self.profile_indication,
+            self.profile_compatibility,
+            self.level_indication,
+            0xfc | self.length_size_minus_one,
+            0xe0 | self.sequence_parameter_sets.len() as u8,
+        ];
+        write_parameter_sets(&mut out, &self.sequence_parameter_sets);
+        out.push(self.picture_parameter_sets.len() as u8);
+        write_parameter_sets(&mut out, &self.picture_parameter_sets);
+
+        if let Some(ext) = &self.high_profile_extension {
+            out.push(0xfc | ext.chroma_format);
+            out.push(0xf8 | ext.bit_depth_luma_minus8);
+            out.push(0xf8 | ext.bit_depth_chroma_minus8);
+            out.push(ext.sequence_parameter_set_ext.len() as u8);
+            write_parameter_sets(&mut out, &ext.sequence_parameter_set_ext);
+        }
+
+        out
+    }
+
+    /// Size in bytes of the NAL unit length prefix used by samples.
+    pub fn length_size(&self) -> u8 {
+        self.length_size_minus_one + 1
+    }
+}
+
+/// Assembles an [`AvcDecoderConfigurationRecord`] from parsed parameter
+/// sets. Profile, level and the High profile extension are taken from the
+/// first SPS; parameter sets are re-escaped from their stored RBSP with
+/// nal_ref_idc 3.
+#[derive(Debug, Clone)]
+pub struct AvcConfigBuilder {
+    length_size: u8,
+    sps: Vec<Arc<Sps>>,
+    pps: Vec<Arc<Pps>>,
+    sps_ext: Vec<Vec<u8>>,
+}
+
+impl AvcConfigBuilder {
+    pub fn new() -> Self {
+        Self {
+            length_size: 4,
+            sps: Vec::new(),
+            pps: Vec::new(),
+            sps_ext: Vec::new(),
+        }
+    }
+
+    /// Starts from the parameter sets attached to `au`, plus any SPS
+    /// extension NAL units it carries in-band.
+    pub fn from_access_unit(au: &AccessUnit) -> Self {
+        let mut builder = Self::new();
+        if let Some(sps) = &au.sps {
+            builder.add_sps(sps.clone());
+        }
+        if let Some(pps) = &au.pps {
+            builder.add_pps(pps.clone());
+        }
+        for nal in au.nals().filter(|nal| nal.nal_type == NalUnitType::SpsExt) {
+            let mut data = vec![nal.header_byte()];
+            data.extend_from_slice(&nal.ebsp);
+            builder.add_sps_ext(data);
+        }
+        builder
+    }
+
+    pub fn set_length_size(&mut self, length_size: u8) {
+        self.length_size = length_size;
+    }
+
+    /// Adds an SPS, replacing any earlier one with the same id.
+    pub fn add_sps(&mut self, sps: Arc<Sps>) {
+        self.sps.retain(|s| s.seq_parameter_set_id != sps.seq_parameter_set_id);
+        self.sps.push(sps);
+    }
+
+    /// Adds a PPS, replacing any earlier one with the same id.
+    pub fn add_pps(&mut self, pps: Arc<Pps>) {
+        self.pps.retain(|p| p.pic_parameter_set_id != pps.pic_parameter_set_id);
+        self.pps.push(pps);
+    }
+
+    /// Adds a complete SPS extension NAL unit, header byte included.
+    pub fn add_sps_ext(&mut self, nal: Vec<u8>) {
+        self.sps_ext.push(nal);
+    }
+
+    pub fn build(&self) -> Result<AvcDecoderConfigurationRecord> {
+        if !matches!(self.length_size, 1 | 2 | 4) {
+            return Err(Error::MalformedAvcc(format!("Invalid NAL length size {}", self.length_size)));
+        }
+
+        let first = self
+            .sps
+            .first()
+            .ok_or_else(|| Error::MalformedAvcc("No SPS".into()))?;
+        if self.pps.is_empty() {
+            return Err(Error::MalformedAvcc("No PPS".into()));
+        }
+        if self.sps.len() > 31 || self.pps.len() > 255 || self.sps_ext.len() > 255 {
+            return Err(Error::MalformedAvcc("Too many parameter sets".into()));
+        }
+
+        let sequence_parameter_sets: Vec<Vec<u8>> = self
+            .sps
+            .iter()
+            .map(|sps| parameter_set_nal(NalUnitType::Sps, &sps.rbsp))
+            .collect();
+        let picture_parameter_sets: Vec<Vec<u8>> = self
+            .pps
+            .iter()
+            .map(|pps| parameter_set_nal(NalUnitType::Pps, &pps.rbsp))
+            .collect();
+        if sequence_parameter_sets
+            .iter()
+            .chain(&picture_parameter_sets)
+            .chain(&self.sps_ext)
+            .any(|nal| nal.len() > u16::MAX as usize)
+        {
+            return Err(Error::MalformedAvcc("Parameter set too large".into()));
+        }
+
+        let high_profile_extension =
+            has_high_profile_extension(first.profile_idc).then(|| HighProfileExtension {
+                chroma_format: first.chroma_format_idc,
+                bit_depth_luma_minus8: first.bit_depth_luma_minus8,
+                bit_depth_chroma_minus8: first.bit_depth_chroma_minus8,
+                sequence_parameter_set_ext: self.sps_ext.clone(),
+            });
+
+        Ok(AvcDecoderConfigurationRecord {
+            configuration_version: 1,
+            profile_indication: first.profile_idc,
+            profile_compatibility: profile_compatibility(first),
+            level_indication: first.level_idc,
+            length_size_minus_one: self.length_size - 1,
+            sequence_parameter_sets,
+            picture_parameter_sets,
+            high_profile_extension,
+        })
+    }
+}
+
+impl Default for AvcConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parameter_set_nal(nal_type: NalUnitType, rbsp: &[u8]) -> Vec<u8> {
+    let mut nal = vec![(3 << 5) | nal_type.as_u8()];
+    nal.extend(rbsp_to_ebsp(rbsp));
+    nal
+}
+
+fn profile_compatibility(sps: &Sps) -> u8 {
+    [
+        sps.constraint_set0_flag,
+        sps.constraint_set1_flag,
+        sps.constraint_set2_flag,
+        sps.constraint_set3_flag,
+        sps.constraint_set4_flag,
+        sps.constraint_set5_flag,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |acc, (i, &flag)| acc | ((flag as u8) << (7 - i)))
+}
+
+fn write_parameter_sets(out: &mut Vec<u8>, sets: &[Vec<u8>]) {
+    for set in sets {
+        out.extend_from_slice(&(set.len() as u16).to_be_bytes());
+        out.extend_from_slice(set);
+    }
+}
+
+struct RecordReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> RecordReader<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        let byte = *self
+            .data
+            .get(self.pos)
+            .ok_or_else(|| Error::MalformedAvcc("Truncated record".into()))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(((self.u8()? as u16) << 8) | self.u8()? as u16)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos + len;
+        let bytes = self
+            .data
+            .get(self.pos..end)
+            .ok_or_else(|| Error::MalformedAvcc("Truncated parameter set".into()))?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn parameter_sets(&mut self, count: usize) -> Result<Vec<Vec<u8>>> {
+        let mut sets = Vec::with_capacity(count);
+        for _ in 0..count {
+            let len = self.u16()? as usize;
+            sets.push(self.bytes(len)?.to_vec());
+        }
+        Ok(sets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_baseline_record() {
+        let data = vec![
+            0x01, 0x42, 0xc0, 0x1f, 0xff, 0xe1, 0x00, 0x04,
+            0x67, 0x42, 0xc0, 0x1f, 0x01, 0x00, 0x03, 0x68,
+            0xce, 0x3c,
+        ];
+        let record = AvcDecoderConfigurationRecord::parse(&data).unwrap();
+
+        assert_eq!(record.profile_indication, 66);
+        assert_eq!(record.level_indication, 31);
+        assert_eq!(record.length_size(), 4);
+        assert_eq!(record.sequence_parameter_sets, vec![vec![0x67, 0x42, 0xc0, 0x1f]]);
+        assert_eq!(record.picture_parameter_sets, vec![vec![0x68, 0xce, 0x3c]]);
+
+        assert!(record.high_profile_extension.is_none());
+        assert_eq!(record.to_bytes(), data);
+
+        assert!(AvcDecoderConfigurationRecord::parse(&data[..10]).is_err());
+    }
+
+    #[test]
+    fn test_parse_high_profile_extension() {
+        let data = vec![
+            0x01, 0x64, 0x00, 0x28, 0xff, 0xe1, 0x00, 0x03,
+            0x67, 0x64, 0x00, 0x01, 0x00, 0x02, 0x68, 0xeb,
+            0xfd, 0xfa, 0xf8, 0x01, 0x00, 0x02, 0x6d, 0x10,
+        ];
+        let record = AvcDecoderConfigurationRecord::parse(&data).unwrap();
+
+        assert_eq!(
+            record.high_profile_extension,
+            Some(HighProfileExtension {
+                chroma_format: 1,
+                bit_depth_luma_minus8: 2,
+                bit_depth_chroma_minus8: 0,
+                sequence_parameter_set_ext: vec![vec![0x6d, 0x10]],
+            })
+        );
+        assert_eq!(record.to_bytes(), data);
+
+        // Extension omitted by the muxer
+        let record = AvcDecoderConfigurationRecord::parse(&data[..16]).unwrap();
+        assert!(record.high_profile_extension.is_none());
+        assert!(AvcDecoderConfigurationRecord::parse(&data[..18]).is_err());
+    }
+
+    #[test]
+    fn test_build_from_access_unit() {
+        use crate::nal::ebsp_to_rbsp;
+
+        let sps_nal = vec![
+            0x67, 0x42, 0x00, 0x1f, 0xac, 0x34, 0xc8, 0x14,
+            0x00, 0x00, 0x03, 0x00, 0x04, 0x00, 0x00, 0x03,
+            0x00, 0xf0, 0x3c, 0x60, 0xc6, 0x58,
+        ];
+        let pps_nal = vec![0x68, 0xee, 0x3c, 0x80];
+
+        let mut au = AccessUnit::new();
+        au.set_sps(Arc::new(Sps::parse(&ebsp_to_rbsp(&sps_nal[1..])).unwrap()));
+        au.set_pps(Arc::new(Pps::parse(&ebsp_to_rbsp(&pps_nal[1..])).unwrap()));
+
+        let mut builder = AvcConfigBuilder::from_access_unit(&au);
+        builder.set_length_size(2);
+        let record = builder.build().unwrap();
+
+        assert_eq!(record.profile_indication, 66);
+        assert_eq!(record.profile_compatibility, 0x00);
+        assert_eq!(record.level_indication, 31);
+        assert_eq!(record.length_size(), 2);
+        assert_eq!(record.sequence_parameter_sets, vec![sps_nal]);
+        assert_eq!(record.picture_parameter_sets, vec![pps_nal]);
+        assert!(record.high_profile_extension.is_none());
+
+        let reparsed = AvcDecoderConfigurationRecord::parse(&record.to_bytes()).unwrap();
+        assert_eq!(reparsed, record);
+
+        assert!(AvcConfigBuilder::new().build().is_err());
+    }
+
+    #[test]
+    fn test_high_444_round_trip() {
+        let data = vec![
+            0x01, 0xf4, 0x00, 0x33, 0xff, 0xe1, 0x00, 0x03,
+            0x67, 0xf4, 0x00, 0x01, 0x00, 0x02, 0x68, 0xeb,
+            0xff, 0xfa, 0xfa, 0x00,
+        ];
+        let record = AvcDecoderConfigurationRecord::parse(&data).unwrap();
+        assert_eq!(
+            record.high_profile_extension,
+            Some(HighProfileExtension {
+                chroma_format: 3,
+                bit_depth_luma_minus8: 2,
+                bit_depth_chroma_minus8: 2,
+                sequence_parameter_set_ext: vec![],
+            })
+        );
+        assert_eq!(record.to_bytes(), data);
+
+        let mut sps = crate::sps::test_sps();
+        sps.profile_idc = 244;
+        sps.chroma_format_idc = 3;
+        sps.bit_depth_luma_minus8 = 2;
+        sps.bit_depth_chroma_minus8 = 2;
+        let mut builder = AvcConfigBuilder::new();
+        builder.add_sps(Arc::new(sps));
+        builder.add_pps(Arc::new(Pps::parse(&[0xee, 0x3c, 0x80]).unwrap()));
+
+        let record = builder.build().unwrap();
+        assert_eq!(record.profile_indication, 244);
+        assert_eq!(record.high_profile_extension.as_ref().unwrap().chroma_format, 3);
+        let reparsed = AvcDecoderConfigurationRecord::parse(&record.to_bytes()).unwrap();
+        assert_eq!(reparsed, record);
+    }
+}
@@ -150,6 +150,21 @@ impl<'a> BitReader<'a> {
     }
 }
 
+// Packs a string of '0'/'1' digits into bytes, ignoring whitespace and
+// zero-padding the final byte
+#[cfg(test)]
+pub(crate) fn pack_bit_string(bits: &str) -> Vec<u8> {
+    let bits: Vec<u8> = bits.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    bits.chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |acc, (i, &b)| acc | (((b == b'1') as u8) << (7 - i)))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -169,8 +184,8 @@ mod tests {
         let data = vec![0b10000000, 0b01000000];
         let mut reader = BitReader::new(&data);
 
-        assert_eq!(reader.read_flag().unwrap(), true);
-        assert_eq!(reader.read_flag().unwrap(), false);
+        assert!(reader.read_flag().unwrap());
+        assert!(!reader.read_flag().unwrap());
     }
 
     #[test]
@@ -90,6 +90,12 @@ impl StartCodeScanner {
     }
 }
 
+impl Default for StartCodeScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct NalSpan {
     pub start_pos: usize,
@@ -102,6 +108,10 @@ impl NalSpan {
     pub fn len(&self) -> usize {
         self.data_end - self.data_start
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.data_end == self.data_start
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,604 @@
+use crate::au::AccessUnit;
+use crate::nal::NalUnitType;
+use crate::slice::{MemoryManagementControlOperation, RefPicListModification, SliceHeader};
+use crate::sps::Sps;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceMarking {
+    Unused,
+    ShortTerm,
+    LongTerm,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferencePicture {
+    pub frame_num: u32,
+    pub frame_num_wrap: i32,
+    pub long_term_frame_idx: Option<u32>,
+    pub top_field: ReferenceMarking,
+    pub bottom_field: ReferenceMarking,
+    pub pic_order_cnt: Option<i32>,
+}
+
+impl ReferencePicture {
+    pub fn is_short_term(&self) -> bool {
+        self.top_field == ReferenceMarking::ShortTerm || self.bottom_field == ReferenceMarking::ShortTerm
+    }
+
+    pub fn is_long_term(&self) -> bool {
+        self.top_field == ReferenceMarking::LongTerm || self.bottom_field == ReferenceMarking::LongTerm
+    }
+
+    fn is_reference(&self) -> bool {
+        self.top_field != ReferenceMarking::Unused || self.bottom_field != ReferenceMarking::Unused
+    }
+
+    fn field(&self, bottom: bool) -> ReferenceMarking {
+        if bottom {
+            self.bottom_field
+        } else {
+            self.top_field
+        }
+    }
+
+    fn set_field(&mut self, bottom: bool, marking: ReferenceMarking) {
+        if bottom {
+            self.bottom_field = marking;
+        } else {
+            self.top_field = marking;
+        }
+    }
+
+    fn set_frame(&mut self, marking: ReferenceMarking) {
+        self.top_field = marking;
+        self.bottom_field = marking;
+    }
+
+    fn unmark_long_term(&mut self) {
+        if self.top_field == ReferenceMarking::LongTerm {
+            self.top_field = ReferenceMarking::Unused;
+        }
+        if self.bottom_field == ReferenceMarking::LongTerm {
+            self.bottom_field = ReferenceMarking::Unused;
+        }
+        self.long_term_frame_idx = None;
+    }
+}
+
+/// Reference picture marking state after an access unit has been applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DpbState {
+    pub pictures: Vec<ReferencePicture>,
+    pub max_long_term_frame_idx: Option<u32>,
+}
+
+impl DpbState {
+    pub fn short_term(&self) -> impl Iterator<Item = &ReferencePicture> {
+        self.pictures.iter().filter(|p| p.is_short_term())
+    }
+
+    pub fn long_term(&self) -> impl Iterator<Item = &ReferencePicture> {
+        self.pictures.iter().filter(|p| p.is_long_term())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DpbViolation {
+    MissingShortTermPicture { pic_num: i32 },
+    MissingLongTermPicture { long_term_pic_num: u32 },
+    LongTermFrameIdxOutOfRange { long_term_frame_idx: u32 },
+    SlidingWindowWithoutShortTerm,
+    TooManyReferenceFrames { count: usize, max_num_ref_frames: u32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PreviousField {
+    frame_num: u32,
+    bottom: bool,
+    reference: bool,
+}
+
+// Per-picture values from 8.2.4.1 needed to resolve picture numbers
+#[derive(Debug, Clone, Copy)]
+struct CurrentPicture {
+    frame_num: u32,
+    field: Option<bool>,
+    max_frame_num: i64,
+}
+
+impl CurrentPicture {
+    fn curr_pic_num(&self) -> i64 {
+        match self.field {
+            Some(_) => 2 * self.frame_num as i64 + 1,
+            None => self.frame_num as i64,
+        }
+    }
+
+    fn max_pic_num(&self) -> i64 {
+        match self.field {
+            Some(_) => 2 * self.max_frame_num,
+            None => self.max_frame_num,
+        }
+    }
+}
+
+/// Simulates the decoded reference picture marking process (8.2.5) over a
+/// sequence of access units, tracking which frames are held as short-term
+/// or long-term references.
+#[derive(Debug, Default)]
+pub struct DecodedPictureBuffer {
+    pictures: Vec<ReferencePicture>,
+    max_long_term_frame_idx: Option<u32>,
+    prev_field: Option<PreviousField>,
+}
+
+impl DecodedPictureBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn state(&self) -> DpbState {
+        DpbState {
+            pictures: self.pictures.clone(),
+            max_long_term_frame_idx: self.max_long_term_frame_idx,
+        }
+    }
+
+    pub fn process(&mut self, au: &AccessUnit) -> Vec<DpbViolation> {
+        match (&au.slice_header, &au.sps, au.first_vcl_nal()) {
+            (Some(header), Some(sps), Some(nal)) => self.process_picture(
+                header,
+                nal.nal_type == NalUnitType::IdrSlice,
+                nal.ref_idc,
+                sps,
+                au.pic_order_cnt(),
+            ),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn process_picture(
+        &mut self,
+        header: &SliceHeader,
+        idr: bool,
+        nal_ref_idc: u8,
+        sps: &Sps,
+        pic_order_cnt: Option<i32>,
+    ) -> Vec<DpbViolation> {
+        let mut violations = Vec::new();
+        let current = CurrentPicture {
+            frame_num: header.frame_num,
+            field: header.field_pic_flag.then_some(header.bottom_field_flag),
+            max_frame_num: 1i64 << (sps.log2_max_frame_num_minus4 + 4),
+        };
+
+        // 8.2.4.1: FrameNumWrap of every short-term reference frame
+        for picture in &mut self.pictures {
+            picture.frame_num_wrap = if picture.frame_num > header.frame_num {
+                (picture.frame_num as i64 - current.max_frame_num) as i32
+            } else {
+                picture.frame_num as i32
+            };
+        }
+
+        let second_field = match (current.field, self.prev_field) {
+            (Some(bottom), Some(prev)) => prev.bottom != bottom && prev.frame_num == header.frame_num,
+            _ => false,
+        };
+        let pair_idx = if second_field && self.prev_field.is_some_and(|p| p.reference) {
+            self.pictures.len().checked_sub(1)
+        } else {
+            None
+        };
+
+        self.check_modifications(header, &current, &mut violations);
+
+        if nal_ref_idc != 0 {
+            let mut current_long_term = None;
+
+            if idr {
+                let long_term_reference_flag = header
+                    .dec_ref_pic_marking
+                    .as_ref()
+                    .is_some_and(|m| m.long_term_reference_flag);
+
+                // The second field of an IDR picture joins the first one
+                if pair_idx.is_none() {
+                    self.pictures.clear();
+                    self.max_long_term_frame_idx = long_term_reference_flag.then_some(0);
+                }
+                if long_term_reference_flag {
+                    current_long_term = Some(0);
+                }
+            } else if let Some(marking) = header
+                .dec_ref_pic_marking
+                .as_ref()
+                .filter(|m| m.adaptive_ref_pic_marking_mode_flag)
+            {
+                for op in &marking.mmcos {
+                    if let Some(idx) = self.apply_mmco(op, &current, pair_idx, &mut violations) {
+                        current_long_term = Some(idx);
+                    }
+                }
+            } else if pair_idx.is_none() {
+                self.sliding_window(sps, &mut violations);
+            }
+
+            let marking = if current_long_term.is_some() {
+                ReferenceMarking::LongTerm
+            } else {
+                ReferenceMarking::ShortTerm
+            };
+            let has_mmco5 = header.dec_ref_pic_marking.as_ref().is_some_and(|m| m.has_mmco5());
+            let frame_num = if has_mmco5 { 0 } else { header.frame_num };
+
+            match pair_idx.and_then(|idx| self.pictures.get_mut(idx)) {
+                Some(picture) => {
+                    picture.set_field(current.field.unwrap_or(false), marking);
+                    if current_long_term.is_some() {
+                        picture.long_term_frame_idx = current_long_term;
+                    }
+                    picture.frame_num = frame_num;
+                    picture.pic_order_cnt = match (picture.pic_order_cnt, pic_order_cnt) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    };
+                }
+                None => {
+                    let mut picture = ReferencePicture {
+                        frame_num,
+                        frame_num_wrap: frame_num as i32,
+                        long_term_frame_idx: current_long_term,
+                        top_field: ReferenceMarking::Unused,
+                        bottom_field: ReferenceMarking::Unused,
+                        pic_order_cnt,
+                    };
+                    match current.field {
+                        Some(bottom) => picture.set_field(bottom, marking),
+                        None => picture.set_frame(marking),
+                    }
+                    self.pictures.push(picture);
+                }
+            }
+
+            self.pictures.retain(|p| p.is_reference());
+
+            let count = self.pictures.len();
+            let max_num_ref_frames = sps.max_num_ref_frames.max(1);
+            if count > max_num_ref_frames as usize {
+                violations.push(DpbViolation::TooManyReferenceFrames { count, max_num_ref_frames });
+            }
+        }
+
+        self.prev_field = match current.field {
+            Some(bottom) if !second_field => Some(PreviousField {
+                frame_num: header.frame_num,
+                bottom,
+                reference: nal_ref_idc != 0,
+            }),
+            _ => None,
+        };
+
+        violations
+    }
+
+    // 8.2.4.3: every picture a modification command points at must still be
+    // marked as a reference
+    fn check_modifications(&self, header: &SliceHeader, current: &CurrentPicture, violations: &mut Vec<DpbViolation>) {
+        let lists = [
+            &header.ref_pic_list_modification_l0,
+            &header.ref_pic_list_modification_l1,
+        ];
+
+        for modifications in lists {
+            let mut pic_num_pred = current.curr_pic_num();
+
+            for modification in modifications {
+                match *modification {
+                    RefPicListModification::SubtractAbsDiffPicNum { abs_diff_pic_num_minus1 }
+                    | RefPicListModification::AddAbsDiffPicNum { abs_diff_pic_num_minus1 } => {
+                        let abs_diff = abs_diff_pic_num_minus1 as i64 + 1;
+                        let max_pic_num = current.max_pic_num();
+                        let no_wrap = if matches!(modification, RefPicListModification::SubtractAbsDiffPicNum { .. }) {
+                            if pic_num_pred - abs_diff < 0 {
+                                pic_num_pred - abs_diff + max_pic_num
+                            } else {
+                                pic_num_pred - abs_diff
+                            }
+                        } else if pic_num_pred + abs_diff >= max_pic_num {
+                            pic_num_pred + abs_diff - max_pic_num
+                        } else {
+                            pic_num_pred + abs_diff
+                        };
+                        pic_num_pred = no_wrap;
+
+                        let pic_num = if no_wrap > current.curr_pic_num() {
+                            no_wrap - max_pic_num
+                        } else {
+                            no_wrap
+                        };
+
+                        if self.find_short_term(pic_num, current).is_none() {
+                            violations.push(DpbViolation::MissingShortTermPicture { pic_num: pic_num as i32 });
+                        }
+                    }
+                    RefPicListModification::LongTermPicNum { long_term_pic_num } => {
+                        if self.find_long_term(long_term_pic_num, current).is_none() {
+                            violations.push(DpbViolation::MissingLongTermPicture { long_term_pic_num });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Returns the long-term frame index assigned to the current picture by
+    // memory_management_control_operation 6
+    fn apply_mmco(
+        &mut self,
+        op: &MemoryManagementControlOperation,
+        current: &CurrentPicture,
+        pair_idx: Option<usize>,
+        violations: &mut Vec<DpbViolation>,
+    ) -> Option<u32> {
+        match *op {
+            MemoryManagementControlOperation::MarkShortTermUnused { difference_of_pic_nums_minus1 } => {
+                let pic_num = current.curr_pic_num() - (difference_of_pic_nums_minus1 as i64 + 1);
+                match self.find_short_term(pic_num, current) {
+                    Some((idx, Some(bottom))) => self.pictures[idx].set_field(bottom, ReferenceMarking::Unused),
+                    Some((idx, None)) => self.pictures[idx].set_frame(ReferenceMarking::Unused),
+                    None => violations.push(DpbViolation::MissingShortTermPicture { pic_num: pic_num as i32 }),
+                }
+            }
+            MemoryManagementControlOperation::MarkLongTermUnused { long_term_pic_num } => {
+                match self.find_long_term(long_term_pic_num, current) {
+                    Some((idx, Some(bottom))) => {
+                        let picture = &mut self.pictures[idx];
+                        picture.set_field(bottom, ReferenceMarking::Unused);
+                        if !picture.is_long_term() {
+                            picture.long_term_frame_idx = None;
+                        }
+                    }
+                    Some((idx, None)) => self.pictures[idx].unmark_long_term(),
+                    None => violations.push(DpbViolation::MissingLongTermPicture { long_term_pic_num }),
+                }
+            }
+            MemoryManagementControlOperation::AssignLongTermFrameIdx {
+                difference_of_pic_nums_minus1,
+                long_term_frame_idx,
+            } => {
+                self.check_long_term_frame_idx(long_term_frame_idx, violations);
+                let pic_num = current.curr_pic_num() - (difference_of_pic_nums_minus1 as i64 + 1);
+                match self.find_short_term(pic_num, current) {
+                    Some((idx, field)) => {
+                        self.release_long_term_frame_idx(long_term_frame_idx, Some(idx));
+                        let picture = &mut self.pictures[idx];
+                        match field {
+                            Some(bottom) => picture.set_field(bottom, ReferenceMarking::LongTerm),
+                            None => picture.set_frame(ReferenceMarking::LongTerm),
+                        }
+                        picture.long_term_frame_idx = Some(long_term_frame_idx);
+                    }
+                    None => violations.push(DpbViolation::MissingShortTermPicture { pic_num: pic_num as i32 }),
+                }
+            }
+            MemoryManagementControlOperation::SetMaxLongTermFrameIdx { max_long_term_frame_idx_plus1 } => {
+                self.max_long_term_frame_idx = max_long_term_frame_idx_plus1.checked_sub(1);
+                let max = self.max_long_term_frame_idx;
+                for picture in &mut self.pictures {
+                    if let Some(idx) = picture.long_term_frame_idx {
+                        if max.is_none_or(|max| idx > max) {
+                            picture.unmark_long_term();
+                        }
+                    }
+                }
+            }
+            MemoryManagementControlOperation::MarkAllUnused => {
+                for picture in &mut self.pictures {
+                    picture.set_frame(ReferenceMarking::Unused);
+                    picture.long_term_frame_idx = None;
+                }
+                self.max_long_term_frame_idx = None;
+            }
+            MemoryManagementControlOperation::MarkCurrentLongTerm { long_term_frame_idx } => {
+                self.check_long_term_frame_idx(long_term_frame_idx, violations);
+                self.release_long_term_frame_idx(long_term_frame_idx, pair_idx);
+                return Some(long_term_frame_idx);
+            }
+        }
+
+        None
+    }
+
+    // 8.2.5.3
+    fn sliding_window(&mut self, sps: &Sps, violations: &mut Vec<DpbViolation>) {
+        let max_num_ref_frames = sps.max_num_ref_frames.max(1) as usize;
+        if self.pictures.len() < max_num_ref_frames {
+            return;
+        }
+
+        let oldest = self
+            .pictures
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.is_short_term())
+            .min_by_key(|(_, p)| p.frame_num_wrap)
+            .map(|(idx, _)| idx);
+
+        match oldest {
+            Some(idx) => {
+                self.pictures.remove(idx);
+            }
+            None => violations.push(DpbViolation::SlidingWindowWithoutShortTerm),
+        }
+    }
+
+    fn check_long_term_frame_idx(&self, long_term_frame_idx: u32, violations: &mut Vec<DpbViolation>) {
+        if self.max_long_term_frame_idx.is_none_or(|max| long_term_frame_idx > max) {
+            violations.push(DpbViolation::LongTermFrameIdxOutOfRange { long_term_frame_idx });
+        }
+    }
+
+    // A LongTermFrameIdx can only belong to one frame (or complementary field pair)
+    fn release_long_term_frame_idx(&mut self, long_term_frame_idx: u32, keep: Option<usize>) {
+        for (idx, picture) in self.pictures.iter_mut().enumerate() {
+            if Some(idx) != keep && picture.long_term_frame_idx == Some(long_term_frame_idx) {
+                picture.unmark_long_term();
+            }
+        }
+    }
+
+    // 8.2.4.1: PicNum of a frame is its FrameNumWrap; a field's is
+    // 2 * FrameNumWrap + 1 for the same parity and 2 * FrameNumWrap otherwise
+    fn find_short_term(&self, pic_num: i64, current: &CurrentPicture) -> Option<(usize, Option<bool>)> {
+        self.pictures.iter().enumerate().find_map(|(idx, p)| {
+            let wrap = p.frame_num_wrap as i64;
+            match current.field {
+                None => (p.top_field == ReferenceMarking::ShortTerm
+                    && p.bottom_field == ReferenceMarking::ShortTerm
+                    && wrap == pic_num)
+                    .then_some((idx, None)),
+                Some(bottom) => [bottom, !bottom].into_iter().find_map(|parity| {
+                    let field_pic_num = 2 * wrap + (parity == bottom) as i64;
+                    (p.field(parity) == ReferenceMarking::ShortTerm && field_pic_num == pic_num)
+                        .then_some((idx, Some(parity)))
+                }),
+            }
+        })
+    }
+
+    fn find_long_term(&self, long_term_pic_num: u32, current: &CurrentPicture) -> Option<(usize, Option<bool>)> {
+        let long_term_pic_num = long_term_pic_num as i64;
+        self.pictures.iter().enumerate().find_map(|(idx, p)| {
+            let lt_idx = p.long_term_frame_idx? as i64;
+            match current.field {
+                None => (p.top_field == ReferenceMarking::LongTerm
+                    && p.bottom_field == ReferenceMarking::LongTerm
+                    && lt_idx == long_term_pic_num)
+                    .then_some((idx, None)),
+                Some(bottom) => [bottom, !bottom].into_iter().find_map(|parity| {
+                    let field_pic_num = 2 * lt_idx + (parity == bottom) as i64;
+                    (p.field(parity) == ReferenceMarking::LongTerm && field_pic_num == long_term_pic_num)
+                        .then_some((idx, Some(parity)))
+                }),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slice::{test_slice_header, DecRefPicMarking};
+    use crate::sps::test_sps;
+
+    fn header(frame_num: u32, mmcos: Vec<MemoryManagementControlOperation>) -> SliceHeader {
+        SliceHeader {
+            dec_ref_pic_marking: Some(DecRefPicMarking {
+                adaptive_ref_pic_marking_mode_flag: !mmcos.is_empty(),
+                mmcos,
+                ..Default::default()
+            }),
+            ..test_slice_header(frame_num)
+        }
+    }
+
+    fn frame_nums(dpb: &DecodedPictureBuffer) -> Vec<u32> {
+        dpb.state().short_term().map(|p| p.frame_num).collect()
+    }
+
+    #[test]
+    fn test_sliding_window_evicts_oldest() {
+        let sps = Sps { max_num_ref_frames: 2, ..test_sps() };
+        let mut dpb = DecodedPictureBuffer::new();
+
+        assert!(dpb.process_picture(&header(0, vec![]), true, 3, &sps, None).is_empty());
+        assert!(dpb.process_picture(&header(1, vec![]), false, 2, &sps, None).is_empty());
+        assert_eq!(frame_nums(&dpb), vec![0, 1]);
+
+        assert!(dpb.process_picture(&header(2, vec![]), false, 2, &sps, None).is_empty());
+        assert_eq!(frame_nums(&dpb), vec![1, 2]);
+
+        // Non-reference pictures never enter the DPB
+        dpb.process_picture(&header(3, vec![]), false, 0, &sps, None);
+        assert_eq!(frame_nums(&dpb), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_sliding_window_uses_frame_num_wrap() {
+        let sps = Sps { max_num_ref_frames: 2, ..test_sps() };
+        let mut dpb = DecodedPictureBuffer::new();
+
+        dpb.process_picture(&header(14, vec![]), true, 3, &sps, None);
+        dpb.process_picture(&header(15, vec![]), false, 2, &sps, None);
+        dpb.process_picture(&header(0, vec![]), false, 2, &sps, None);
+        assert_eq!(frame_nums(&dpb), vec![15, 0]);
+    }
+
+    #[test]
+    fn test_mmco_long_term_and_evicted_reference() {
+        let sps = Sps { max_num_ref_frames: 4, ..test_sps() };
+        let mut dpb = DecodedPictureBuffer::new();
+
+        dpb.process_picture(&header(0, vec![]), true, 3, &sps, None);
+        dpb.process_picture(
+            &header(1, vec![
+                MemoryManagementControlOperation::SetMaxLongTermFrameIdx { max_long_term_frame_idx_plus1: 1 },
+                MemoryManagementControlOperation::MarkCurrentLongTerm { long_term_frame_idx: 0 },
+            ]),
+            false,
+            2,
+            &sps,
+            None,
+        );
+
+        let state = dpb.state();
+        assert_eq!(state.short_term().count(), 1);
+        let long_term: Vec<_> = state.long_term().collect();
+        assert_eq!(long_term.len(), 1);
+        assert_eq!(long_term[0].long_term_frame_idx, Some(0));
+
+        // Drop frame 0, then try to drop it again
+        let violations = dpb.process_picture(
+            &header(2, vec![MemoryManagementControlOperation::MarkShortTermUnused { difference_of_pic_nums_minus1: 1 }]),
+            false,
+            2,
+            &sps,
+            None,
+        );
+        assert!(violations.is_empty());
+        let violations = dpb.process_picture(
+            &header(3, vec![MemoryManagementControlOperation::MarkShortTermUnused { difference_of_pic_nums_minus1: 2 }]),
+            false,
+            2,
+            &sps,
+            None,
+        );
+        assert_eq!(violations, vec![DpbViolation::MissingShortTermPicture { pic_num: 0 }]);
+
+        let mut modified = header(4, vec![]);
+        modified.ref_pic_list_modification_l0 = vec![
+            RefPicListModification::SubtractAbsDiffPicNum { abs_diff_pic_num_minus1: 0 },
+            RefPicListModification::LongTermPicNum { long_term_pic_num: 1 },
+        ];
+        let violations = dpb.process_picture(&modified, false, 2, &sps, None);
+        assert_eq!(violations, vec![DpbViolation::MissingLongTermPicture { long_term_pic_num: 1 }]);
+    }
+
+    #[test]
+    fn test_mmco5_clears_references() {
+        let sps = Sps { max_num_ref_frames: 4, ..test_sps() };
+        let mut dpb = DecodedPictureBuffer::new();
+
+        dpb.process_picture(&header(0, vec![]), true, 3, &sps, None);
+        dpb.process_picture(&header(1, vec![]), false, 2, &sps, None);
+        dpb.process_picture(&header(2, vec![MemoryManagementControlOperation::MarkAllUnused]), false, 2, &sps, None);
+
+        // The current picture is kept with frame_num treated as 0
+        assert_eq!(frame_nums(&dpb), vec![0]);
+    }
+}
@@ -77,10 +77,7 @@ pub fn write_ue(value: u32) -> Vec<bool> {
     let total_bits = 2 * num_bits - 1;
     
     let mut bits = Vec::with_capacity(total_bits as usize);
-    
-    for _ in 0..(num_bits - 1) {
-        bits.push(false);
-    }
+    bits.resize((num_bits - 1) as usize, false);
     
     for i in (0..num_bits).rev() {
         bits.push((code_num >> i) & 1 != 0);
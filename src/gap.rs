@@ -0,0 +1,128 @@
+use crate::au::AccessUnit;
+use crate::nal::NalUnitType;
+use crate::slice::SliceHeader;
+use crate::sps::Sps;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameNumGap {
+    pub prev_ref_frame_num: u32,
+    pub frame_num: u32,
+    pub missing_frames: u32,
+}
+
+/// Tracks PrevRefFrameNum (7.4.3) and reports jumps in frame_num that the
+/// active SPS does not allow, which usually means reference pictures were
+/// lost in transport.
+#[derive(Debug, Default)]
+pub struct FrameNumGapDetector {
+    prev_ref_frame_num: Option<u32>,
+}
+
+impl FrameNumGapDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&mut self) {
+        self.prev_ref_frame_num = None;
+    }
+
+    pub fn process(&mut self, au: &AccessUnit) -> Option<FrameNumGap> {
+        let header = au.slice_header.as_ref()?;
+        let sps = au.sps.as_ref()?;
+        let nal = au.first_vcl_nal()?;
+
+        self.check(header, nal.nal_type == NalUnitType::IdrSlice, nal.ref_idc, sps)
+    }
+
+    pub fn check(
+        &mut self,
+        header: &SliceHeader,
+        idr: bool,
+        nal_ref_idc: u8,
+        sps: &Sps,
+    ) -> Option<FrameNumGap> {
+        let max_frame_num = 1u32 << (sps.log2_max_frame_num_minus4 + 4);
+        let frame_num = header.frame_num;
+
+        let gap = match self.prev_ref_frame_num {
+            Some(prev) if !idr && !sps.gaps_in_frame_num_value_allowed_flag => {
+                let expected = (prev + 1) % max_frame_num;
+                if frame_num != prev && frame_num != expected {
+                    Some(FrameNumGap {
+                        prev_ref_frame_num: prev,
+                        frame_num,
+                        missing_frames: (frame_num + max_frame_num - expected) % max_frame_num,
+                    })
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        let has_mmco5 = header
+            .dec_ref_pic_marking
+            .as_ref()
+            .is_some_and(|m| m.has_mmco5());
+
+        if idr || has_mmco5 {
+            self.prev_ref_frame_num = Some(0);
+        } else if nal_ref_idc != 0 || gap.is_some() {
+            // After a gap, resynchronise on the picture that revealed it so a
+            // single loss is only reported once
+            self.prev_ref_frame_num = Some(if nal_ref_idc != 0 {
+                frame_num
+            } else {
+                (frame_num + max_frame_num - 1) % max_frame_num
+            });
+        }
+
+        gap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slice::test_slice_header;
+    use crate::sps::test_sps;
+
+    #[test]
+    fn test_detects_missing_reference_frames() {
+        let sps = test_sps();
+        let mut detector = FrameNumGapDetector::new();
+
+        assert_eq!(detector.check(&test_slice_header(0), true, 3, &sps), None);
+        assert_eq!(detector.check(&test_slice_header(1), false, 2, &sps), None);
+        // Non-reference pictures share the next frame_num
+        assert_eq!(detector.check(&test_slice_header(2), false, 0, &sps), None);
+        assert_eq!(detector.check(&test_slice_header(2), false, 0, &sps), None);
+        assert_eq!(detector.check(&test_slice_header(2), false, 2, &sps), None);
+
+        assert_eq!(
+            detector.check(&test_slice_header(5), false, 2, &sps),
+            Some(FrameNumGap { prev_ref_frame_num: 2, frame_num: 5, missing_frames: 2 })
+        );
+        assert_eq!(detector.check(&test_slice_header(6), false, 2, &sps), None);
+
+        // MaxFrameNum is 16
+        assert_eq!(
+            detector.check(&test_slice_header(1), false, 0, &sps),
+            Some(FrameNumGap { prev_ref_frame_num: 6, frame_num: 1, missing_frames: 10 })
+        );
+        assert_eq!(detector.check(&test_slice_header(1), false, 0, &sps), None);
+    }
+
+    #[test]
+    fn test_gaps_allowed_and_idr() {
+        let mut sps = test_sps();
+        let mut detector = FrameNumGapDetector::new();
+
+        detector.check(&test_slice_header(0), true, 3, &sps);
+        assert!(detector.check(&test_slice_header(7), true, 3, &sps).is_none());
+
+        sps.gaps_in_frame_num_value_allowed_flag = true;
+        assert!(detector.check(&test_slice_header(4), false, 2, &sps).is_none());
+    }
+}
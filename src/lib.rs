@@ -1,19 +1,42 @@
 pub mod au;
+pub mod avcc;
 pub mod bitreader;
 pub mod bytescan;
+pub mod dpb;
 pub mod eg;
+pub mod gap;
 pub mod nal;
 pub mod parser;
+pub mod poc;
 pub mod pps;
+pub mod reorder;
 pub mod sei;
 pub mod slice;
 pub mod sps;
+pub mod timecode;
+pub mod vui;
 
-pub use au::{AccessUnit, AccessUnitKind};
+pub use au::{AccessUnit, AccessUnitKind, AvccWriteOptions};
+pub use avcc::{AvcConfigBuilder, AvcDecoderConfigurationRecord, HighProfileExtension};
+pub use dpb::{DecodedPictureBuffer, DpbState, DpbViolation};
+pub use gap::{FrameNumGap, FrameNumGapDetector};
 pub use nal::{Nal, NalUnitType};
-pub use parser::AnnexBParser;
+pub use parser::{AnnexBParser, AvccParser};
+pub use poc::{PicOrderCnt, PocCalculator};
 pub use pps::Pps;
-pub use sps::Sps;
+pub use reorder::{OutputTiming, ReorderBuffer};
+pub use sei::{
+    Afd, AmbientViewingEnvironment, AtscUserData, BarData, BufferingPeriod, CaptionPacket, CcData,
+    CcType, ClockTimestamp, ContentLightLevelInfo, CustomSei, DecRefPicMarkingRepetition,
+    DisplayOrientation, EncoderInfo, FilmGrainCharacteristics, FramePackingArrangement,
+    FramePackingType, HdrMetadata, InitialCpbRemovalDelay, KlvLocalSet,
+    MasteringDisplayColourVolume, MvcNestingTarget, MvcScalableNesting, PanScanRect, PicStruct,
+    PicTiming, PrecisionTimeStamp, ScalableNesting, SeiHandlerKey, SeiHandlerRegistry, SeiMessage,
+    SeiPayload, ToneMapModel, ToneMappingInfo, UserDataRegistered,
+};
+pub use sps::{FrameRate, Sps};
+pub use timecode::{Timecode, TimecodeTracker};
+pub use vui::{HrdParameters, Vui};
 
 use std::error::Error as StdError;
 use std::fmt;
@@ -23,6 +46,7 @@ pub enum Error {
     InvalidNalHeader,
     MalformedSps(String),
     MalformedPps(String),
+    MalformedAvcc(String),
     SliceParseError(String),
     MissingPps(u8),
     MissingSps(u8),
@@ -37,6 +61,7 @@ impl fmt::Display for Error {
             Error::InvalidNalHeader => write!(f, "Invalid NAL header"),
             Error::MalformedSps(msg) => write!(f, "Malformed SPS: {}", msg),
             Error::MalformedPps(msg) => write!(f, "Malformed PPS: {}", msg),
+            Error::MalformedAvcc(msg) => write!(f, "Malformed avcC: {}", msg),
             Error::SliceParseError(msg) => write!(f, "Slice parse error: {}", msg),
             Error::MissingPps(id) => write!(f, "Missing PPS with id {}", id),
             Error::MissingSps(id) => write!(f, "Missing SPS with id {}", id),
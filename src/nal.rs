@@ -115,6 +115,10 @@ impl Nal {
         })
     }
 
+    pub fn header_byte(&self) -> u8 {
+        ((self.ref_idc & 0b11) << 5) | (self.nal_type.as_u8() & 0b11111)
+    }
+
     pub fn to_rbsp(&self) -> Vec<u8> {
         ebsp_to_rbsp(&self.ebsp)
     }
@@ -131,14 +135,14 @@ impl Nal {
     }
 
     pub fn is_vcl(&self) -> bool {
-        match self.nal_type {
+        matches!(
+            self.nal_type,
             NalUnitType::NonIdrSlice
-            | NalUnitType::DataPartitionA
-            | NalUnitType::DataPartitionB
-            | NalUnitType::DataPartitionC
-            | NalUnitType::IdrSlice => true,
-            _ => false,
-        }
+                | NalUnitType::DataPartitionA
+                | NalUnitType::DataPartitionB
+                | NalUnitType::DataPartitionC
+                | NalUnitType::IdrSlice
+        )
     }
 }
 
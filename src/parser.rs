@@ -1,29 +1,30 @@
 use crate::au::{AccessUnit, AccessUnitBuilder};
-use crate::bytescan::{NalSpan, StartCodeScanner};
+use crate::avcc::AvcDecoderConfigurationRecord;
+use crate::bytescan::StartCodeScanner;
+use crate::dpb::DecodedPictureBuffer;
+use crate::gap::FrameNumGapDetector;
 use crate::nal::{Nal, NalUnitType};
+use crate::poc::PocCalculator;
 use crate::pps::Pps;
+use crate::reorder::ReorderBuffer;
+use crate::sei::{EncoderInfo, HdrMetadata, SeiHandlerRegistry};
 use crate::slice::SliceHeader;
 use crate::sps::Sps;
+use crate::timecode::TimecodeTracker;
 use crate::{Error, Result};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
 
 pub struct AnnexBParser {
     scanner: StartCodeScanner,
-    au_builder: AccessUnitBuilder,
-    sps_map: HashMap<u8, Arc<Sps>>,
-    pps_map: HashMap<u8, Arc<Pps>>,
-    pending_nals: Vec<(NalSpan, Vec<u8>)>,
+    state: StreamState,
 }
 
 impl AnnexBParser {
     pub fn new() -> Self {
         Self {
             scanner: StartCodeScanner::new(),
-            au_builder: AccessUnitBuilder::new(),
-            sps_map: HashMap::new(),
-            pps_map: HashMap::new(),
-            pending_nals: Vec::new(),
+            state: StreamState::new(),
         }
     }
 
@@ -32,104 +33,359 @@ impl AnnexBParser {
     }
 
     pub fn next_access_unit(&mut self) -> Result<Option<AccessUnit>> {
+        let scanner = &mut self.scanner;
+        self.state.next_access_unit(|| next_annexb_nal(scanner))
+    }
+
+    /// Like [`next_access_unit`](Self::next_access_unit), but returns access
+    /// units in presentation order with [`OutputTiming`](crate::OutputTiming)
+    /// filled in. Pictures are held back until the reorder depth signalled by
+    /// the active SPS guarantees nothing earlier in output order can follow.
+    pub fn next_output_access_unit(&mut self) -> Result<Option<AccessUnit>> {
+        let scanner = &mut self.scanner;
+        self.state.next_output_access_unit(|| next_annexb_nal(scanner))
+    }
+
+    pub fn drain(mut self) -> impl Iterator<Item = Result<AccessUnit>> {
+        let scanner = &mut self.scanner;
+        self.state.drain(|| next_annexb_nal(scanner)).into_iter()
+    }
+
+    /// The latest HDR static metadata seen in decoding order.
+    pub fn hdr_metadata(&self) -> &HdrMetadata {
+        &self.state.hdr_metadata
+    }
+
+    /// The encoder settings from the most recent x264 info SEI.
+    pub fn encoder_info(&self) -> Option<&EncoderInfo> {
+        self.state.encoder_info.as_ref()
+    }
+
+    /// Decoders for proprietary SEI payloads, applied to every access unit.
+    pub fn sei_handlers_mut(&mut self) -> &mut SeiHandlerRegistry {
+        &mut self.state.sei_handlers
+    }
+
+    pub fn reset(&mut self) {
+        self.scanner.reset();
+        self.state.reset();
+    }
+}
+
+impl Default for AnnexBParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn next_annexb_nal(scanner: &mut StartCodeScanner) -> Result<Option<Nal>> {
+    let Some(nal_span) = scanner.next_nal_unit()? else {
+        return Ok(None);
+    };
+    let nal_data = scanner.get_nal_data(&nal_span).to_vec();
+    Nal::parse(nal_span.start_code_len, &nal_data).map(Some)
+}
+
+/// Parser for length-prefixed NAL units as stored in MP4 and Matroska
+/// samples (ISO/IEC 14496-15). Parameter sets usually arrive out of band in
+/// an [`AvcDecoderConfigurationRecord`]; access units are assembled exactly as
+/// [`AnnexBParser`] does.
+pub struct AvccParser {
+    length_size: u8,
+    queue: VecDeque<Vec<u8>>,
+    state: StreamState,
+}
+
+impl AvccParser {
+    pub fn new(length_size: u8) -> Result<Self> {
+        if !matches!(length_size, 1 | 2 | 4) {
+            return Err(Error::BitstreamError(format!(
+                "Invalid NAL length size {}",
+                length_size
+            )));
+        }
+        
+        Ok(Self {
+            length_size,
+            queue: VecDeque::new(),
+            state: StreamState::new(),
+        })
+    }
+
+    pub fn from_config(config: &AvcDecoderConfigurationRecord) -> Result<Self> {
+        let mut parser = Self::new(config.length_size())?;
+        parser.set_config(config)?;
+        Ok(parser)
+    }
+
+    /// Registers the parameter sets carried by a (new) decoder configuration.
+    pub fn set_config(&mut self, config: &AvcDecoderConfigurationRecord) -> Result<()> {
+        for data in config
+            .sequence_parameter_sets
+            .iter()
+            .chain(&config.picture_parameter_sets)
+        {
+            self.state.store_parameter_set(&Nal::parse(4, data)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn length_size(&self) -> u8 {
+        self.length_size
+    }
+
+    /// Splits one sample into NAL units and queues them for parsing.
+    pub fn push_sample(&mut self, sample: &[u8]) -> Result<()> {
+        let length_size = self.length_size as usize;
+        let mut nals = Vec::new();
+        let mut pos = 0;
+        
+        while pos < sample.len() {
+            let prefix = sample
+                .get(pos..pos + length_size)
+                .ok_or(Error::UnexpectedEof)?;
+            let len = prefix.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+            pos += length_size;
+            
+            let data = sample.get(pos..pos + len).ok_or_else(|| {
+                Error::BitstreamError(format!("NAL length {} exceeds sample", len))
+            })?;
+            pos += len;
+            
+            if !data.is_empty() {
+                nals.push(data.to_vec());
+            }
+        }
+        
+        self.queue.extend(nals);
+        Ok(())
+    }
+
+    pub fn next_access_unit(&mut self) -> Result<Option<AccessUnit>> {
+        let queue = &mut self.queue;
+        self.state.next_access_unit(|| next_queued_nal(queue))
+    }
+
+    /// Presentation-order counterpart of
+    /// [`next_access_unit`](Self::next_access_unit); see
+    /// [`AnnexBParser::next_output_access_unit`].
+    pub fn next_output_access_unit(&mut self) -> Result<Option<AccessUnit>> {
+        let queue = &mut self.queue;
+        self.state.next_output_access_unit(|| next_queued_nal(queue))
+    }
+
+    pub fn drain(mut self) -> impl Iterator<Item = Result<AccessUnit>> {
+        let queue = &mut self.queue;
+        self.state.drain(|| next_queued_nal(queue)).into_iter()
+    }
+
+    /// The latest HDR static metadata seen in decoding order.
+    pub fn hdr_metadata(&self) -> &HdrMetadata {
+        &self.state.hdr_metadata
+    }
+
+    /// The encoder settings from the most recent x264 info SEI.
+    pub fn encoder_info(&self) -> Option<&EncoderInfo> {
+        self.state.encoder_info.as_ref()
+    }
+
+    /// Decoders for proprietary SEI payloads, applied to every access unit.
+    pub fn sei_handlers_mut(&mut self) -> &mut SeiHandlerRegistry {
+        &mut self.state.sei_handlers
+    }
+
+    pub fn reset(&mut self) {
+        self.queue.clear();
+        self.state.reset();
+    }
+}
+
+fn next_queued_nal(queue: &mut VecDeque<Vec<u8>>) -> Result<Option<Nal>> {
+    queue.pop_front().map(|data| Nal::parse(4, &data)).transpose()
+}
+
+/// Parameter set tables, access unit assembly and the per-picture models
+/// shared by every input format.
+struct StreamState {
+    au_builder: AccessUnitBuilder,
+    sps_map: HashMap<u8, Arc<Sps>>,
+    pps_map: HashMap<u8, Arc<Pps>>,
+    poc: PocCalculator,
+    dpb: DecodedPictureBuffer,
+    frame_num_gaps: FrameNumGapDetector,
+    timecodes: TimecodeTracker,
+    hdr_metadata: HdrMetadata,
+    encoder_info: Option<EncoderInfo>,
+    sei_handlers: SeiHandlerRegistry,
+    reorder: ReorderBuffer,
+}
+
+impl StreamState {
+    fn new() -> Self {
+        Self {
+            au_builder: AccessUnitBuilder::new(),
+            sps_map: HashMap::new(),
+            pps_map: HashMap::new(),
+            poc: PocCalculator::new(),
+            dpb: DecodedPictureBuffer::new(),
+            frame_num_gaps: FrameNumGapDetector::new(),
+            timecodes: TimecodeTracker::new(),
+            hdr_metadata: HdrMetadata::default(),
+            encoder_info: None,
+            sei_handlers: SeiHandlerRegistry::new(),
+            reorder: ReorderBuffer::new(),
+        }
+    }
+
+    /// Feeds NAL units from `next_nal` until an access unit is complete,
+    /// flushing the last one once the input runs dry.
+    fn next_access_unit(
+        &mut self,
+        mut next_nal: impl FnMut() -> Result<Option<Nal>>,
+    ) -> Result<Option<AccessUnit>> {
+        while let Some(nal) = next_nal()? {
+            if let Some(au) = self.process_nal(nal)? {
+                return Ok(Some(au));
+            }
+        }
+        Ok(self.flush())
+    }
+
+    fn next_output_access_unit(
+        &mut self,
+        mut next_nal: impl FnMut() -> Result<Option<Nal>>,
+    ) -> Result<Option<AccessUnit>> {
         loop {
-            let nal_span_result = self.scanner.next_nal_unit()?;
-            // eprintln!("Scanner returned: {:?}", nal_span_result.as_ref().map(|s| (s.start_pos, s.data_end)));
-            if let Some(nal_span) = nal_span_result {
-                let nal_data = self.scanner.get_nal_data(&nal_span).to_vec();
-                
-                let nal = Nal::parse(nal_span.start_code_len, &nal_data)?;
-                
-                match nal.nal_type {
-                    NalUnitType::Sps => {
-                        let rbsp = nal.to_rbsp();
-                        let sps = Sps::parse(&rbsp)?;
-                        let sps_id = sps.seq_parameter_set_id;
-                        self.sps_map.insert(sps_id, Arc::new(sps));
-                    }
-                    NalUnitType::Pps => {
-                        let rbsp = nal.to_rbsp();
-                        let pps = Pps::parse(&rbsp)?;
-                        let pps_id = pps.pic_parameter_set_id;
-                        self.pps_map.insert(pps_id, Arc::new(pps));
-                    }
-                    _ => {}
-                }
-                
-                let mut slice_header = None;
-                let mut sps = None;
-                let mut pps = None;
-                
-                if nal.is_slice() {
-                    let rbsp = nal.to_rbsp();
-                    
-                    let temp_header = parse_slice_header_minimal(&rbsp)?;
-                    let pps_id = temp_header.0;
-                    
-                    if let Some(pps_ref) = self.pps_map.get(&pps_id) {
-                        pps = Some(pps_ref.clone());
-                        let sps_id = pps_ref.seq_parameter_set_id;
-                        
-                        if let Some(sps_ref) = self.sps_map.get(&sps_id) {
-                            sps = Some(sps_ref.clone());
-                            
-                            slice_header = Some(SliceHeader::parse(
-                                &rbsp,
-                                nal.nal_type,
-                                &sps_ref,
-                                &pps_ref,
-                            )?);
-                        } else {
-                            return Err(Error::MissingSps(sps_id));
-                        }
-                    } else {
-                        return Err(Error::MissingPps(pps_id));
-                    }
-                }
-                
-                // Since Nal now owns its data, we can just use the parsed nal directly
-                let owned_nal = nal.clone();
-                
-                if let Some(au) = self.au_builder.add_nal(owned_nal, slice_header, sps, pps) {
-                    return Ok(Some(au));
-                }
-            } else {
-                // When scanner returns None, we need to flush any pending AU
-                // from the builder before returning None
-                if let Some(au) = self.au_builder.flush_pending() {
-                    return Ok(Some(au));
+            if let Some(au) = self.reorder.pop() {
+                return Ok(Some(au));
+            }
+            
+            match self.next_access_unit(&mut next_nal)? {
+                Some(au) => self.reorder.push(au),
+                None => {
+                    self.reorder.flush();
+                    return Ok(self.reorder.pop());
                 }
-                return Ok(None);
             }
         }
     }
 
-    pub fn drain(mut self) -> impl Iterator<Item = Result<AccessUnit>> {
+    fn drain(
+        &mut self,
+        mut next_nal: impl FnMut() -> Result<Option<Nal>>,
+    ) -> Vec<Result<AccessUnit>> {
         let mut results = Vec::new();
         
-        while let Ok(Some(au)) = self.next_access_unit() {
+        while let Ok(Some(au)) = self.next_access_unit(&mut next_nal) {
             results.push(Ok(au));
         }
         
-        if let Some(au) = self.au_builder.flush() {
+        if let Some(au) = self.flush() {
             results.push(Ok(au));
         }
         
-        results.into_iter()
+        results
     }
 
-    pub fn reset(&mut self) {
-        self.scanner.reset();
-        self.au_builder = AccessUnitBuilder::new();
-        self.sps_map.clear();
-        self.pps_map.clear();
-        self.pending_nals.clear();
+    fn store_parameter_set(&mut self, nal: &Nal) -> Result<()> {
+        match nal.nal_type {
+            NalUnitType::Sps => {
+                let rbsp = nal.to_rbsp();
+                let sps = Sps::parse(&rbsp)?;
+                let sps_id = sps.seq_parameter_set_id;
+                self.sps_map.insert(sps_id, Arc::new(sps));
+            }
+            NalUnitType::Pps => {
+                let rbsp = nal.to_rbsp();
+                let pps = Pps::parse(&rbsp)?;
+                let pps_id = pps.pic_parameter_set_id;
+                self.pps_map.insert(pps_id, Arc::new(pps));
+            }
+            _ => {}
+        }
+        Ok(())
     }
-}
 
-impl Default for AnnexBParser {
-    fn default() -> Self {
-        Self::new()
+    fn process_nal(&mut self, nal: Nal) -> Result<Option<AccessUnit>> {
+        self.store_parameter_set(&nal)?;
+        
+        let mut slice_header = None;
+        let mut sps = None;
+        let mut pps = None;
+        
+        if nal.is_slice() {
+            let rbsp = nal.to_rbsp();
+            
+            let temp_header = parse_slice_header_minimal(&rbsp)?;
+            let pps_id = temp_header.0;
+            
+            if let Some(pps_ref) = self.pps_map.get(&pps_id) {
+                pps = Some(pps_ref.clone());
+                let sps_id = pps_ref.seq_parameter_set_id;
+                
+                if let Some(sps_ref) = self.sps_map.get(&sps_id) {
+                    sps = Some(sps_ref.clone());
+                    
+                    slice_header = Some(SliceHeader::parse(
+                        &rbsp,
+                        nal.nal_type,
+                        nal.ref_idc,
+                        sps_ref,
+                        pps_ref,
+                    )?);
+                } else {
+                    return Err(Error::MissingSps(sps_id));
+                }
+            } else {
+                return Err(Error::MissingPps(pps_id));
+            }
+        }
+        
+        Ok(self
+            .au_builder
+            .add_nal(nal, slice_header, sps, pps)
+            .map(|au| self.finish_access_unit(au)))
+    }
+
+    fn flush(&mut self) -> Option<AccessUnit> {
+        self.au_builder
+            .flush_pending()
+            .map(|au| self.finish_access_unit(au))
+    }
+
+    fn finish_access_unit(&mut self, mut au: AccessUnit) -> AccessUnit {
+        if let (Some(header), Some(sps), Some(nal)) = (&au.slice_header, &au.sps, au.first_vcl_nal()) {
+            au.poc = Some(self.poc.compute(header, nal.nal_type, nal.ref_idc, sps));
+        }
+        
+        if au.slice_header.is_some() {
+            au.frame_num_gap = self.frame_num_gaps.process(&au);
+            au.dpb_violations = self.dpb.process(&au);
+            au.reference_state = Some(self.dpb.state());
+        }
+        
+        au.timecode = self.timecodes.process(&au);
+        
+        if au.first_vcl_nal().is_some_and(|nal| nal.nal_type == NalUnitType::IdrSlice) {
+            self.hdr_metadata.reset();
+        }
+        self.hdr_metadata.update(&au.sei_messages);
+        au.hdr_metadata = self.hdr_metadata;
+        
+        if let Some(info) = au.encoder_info() {
+            self.encoder_info = Some(info);
+        }
+        
+        au.custom_sei = self.sei_handlers.decode_access_unit(&au);
+        
+        au
+    }
+
+    fn reset(&mut self) {
+        // Registered handlers are configuration, not stream state
+        let sei_handlers = std::mem::take(&mut self.sei_handlers);
+        *self = Self::new();
+        self.sei_handlers = sei_handlers;
     }
 }
 
@@ -157,8 +413,8 @@ mod tests {
     #[test]
     fn test_parser_creation() {
         let parser = AnnexBParser::new();
-        assert_eq!(parser.sps_map.len(), 0);
-        assert_eq!(parser.pps_map.len(), 0);
+        assert_eq!(parser.state.sps_map.len(), 0);
+        assert_eq!(parser.state.pps_map.len(), 0);
     }
 
     #[test]
@@ -180,6 +436,128 @@ mod tests {
         
         parser.push(&pps_data);
         
-        assert!(parser.sps_map.len() > 0 || parser.pps_map.len() > 0 || true);
+        while let Ok(Some(_au)) = parser.next_access_unit() {}
+        assert_eq!(parser.state.sps_map.len(), 1);
+        assert_eq!(parser.state.pps_map.len(), 1);
+    }
+
+    #[test]
+    fn test_avcc_sample_splitting() {
+        let mut parser = AvccParser::new(2).unwrap();
+        
+        parser.push_sample(&[0x00, 0x02, 0x09, 0xf0, 0x00, 0x00]).unwrap();
+        assert_eq!(parser.queue.len(), 1);
+        
+        assert!(parser.push_sample(&[0x00, 0x05, 0x09, 0xf0]).is_err());
+        assert!(parser.push_sample(&[0x00]).is_err());
+        assert!(AvccParser::new(3).is_err());
+    }
+
+    #[test]
+    fn test_access_unit_grouping() {
+        let sps = [0x00, 0x00, 0x00, 0x01, 0x67, 0x42, 0x00, 0x1e, 0xdd, 0xc4];
+        let pps = [0x00, 0x00, 0x00, 0x01, 0x68, 0xce, 0x38, 0x80];
+        let sei = [0x00, 0x00, 0x00, 0x01, 0x06, 0x06, 0x01, 0x84, 0x80];
+        let idr = [0x00, 0x00, 0x00, 0x01, 0x65, 0xb8, 0x4e];
+
+        // Parameter sets and SEI belong to the picture that follows them,
+        // a trailing SEI without a picture still forms an access unit
+        let mut parser = AnnexBParser::new();
+        for nal in [&sps[..], &pps, &sei, &idr, &sei, &idr, &sei] {
+            parser.push(nal);
+        }
+        let aus: Vec<Vec<NalUnitType>> = parser
+            .drain()
+            .map(|au| au.unwrap().nals().map(|nal| nal.nal_type).collect())
+            .collect();
+        assert_eq!(
+            aus,
+            [
+                vec![NalUnitType::Sps, NalUnitType::Pps, NalUnitType::Sei, NalUnitType::IdrSlice],
+                vec![NalUnitType::Sei, NalUnitType::IdrSlice],
+                vec![NalUnitType::Sei],
+            ]
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_registered_sei_handlers() {
+        use crate::sei::SeiHandlerKey;
+
+        let uuid = *b"example-uuid-000";
+        let mut parser = AnnexBParser::new();
+        parser.sei_handlers_mut().register(SeiHandlerKey::Uuid(uuid), |data| {
+            Ok(String::from_utf8_lossy(data).into_owned())
+        });
+        parser.reset();
+
+        let mut stream = vec![0x00, 0x00, 0x00, 0x01, 0x09, 0xf0];
+        stream.extend_from_slice(&[0x00, 0x00, 0x00, 0x01, 0x06, 0x05, 0x12]);
+        stream.extend_from_slice(&uuid);
+        stream.extend_from_slice(b"hi");
+        stream.push(0x80);
+        parser.push(&stream);
+
+        let au = parser.drain().next().unwrap().unwrap();
+        assert_eq!(au.custom_sei.len(), 1);
+        assert_eq!(au.custom_sei[0].key, SeiHandlerKey::Uuid(uuid));
+        assert_eq!(au.custom_sei_values::<String>().collect::<Vec<_>>(), ["hi"]);
+    }
+
+    #[test]
+    fn test_encoder_info() {
+        use crate::sei::SeiMessage;
+
+        let message = SeiMessage::user_data_unregistered(
+            crate::sei::UUID_X264,
+            b"x264 - core 164 r3095 baf4d4b - H.264/MPEG-4 AVC codec - options: cabac=1 ref=3\0",
+        );
+        let mut au = AccessUnit::new();
+        au.add_nal(Nal::parse(4, &[0x09, 0xf0]).unwrap());
+        au.insert_sei(&[message]).unwrap();
+
+        let mut parser = AnnexBParser::new();
+        assert!(parser.encoder_info().is_none());
+        parser.push(&au.to_annexb_bytes());
+        parser.push(&[0x00, 0x00, 0x00, 0x01, 0x09, 0xf0]);
+        while let Ok(Some(_)) = parser.next_access_unit() {}
+
+        let info = parser.encoder_info().unwrap();
+        assert_eq!(info.core, Some(164));
+        assert_eq!(info.option("ref"), Some("3"));
+    }
+
+    #[test]
+    fn test_hdr_metadata_resets_at_idr() {
+        let aud = [0x00, 0x00, 0x00, 0x01, 0x09, 0xf0];
+        let idr = [0x00, 0x00, 0x00, 0x01, 0x65, 0xb8, 0x4e];
+        let mut stream = aud.to_vec();
+        stream.extend_from_slice(&[
+            0x00, 0x00, 0x00, 0x01, 0x67, 0x42, 0x00, 0x1e,
+            0xdd, 0xc4,
+            0x00, 0x00, 0x00, 0x01, 0x68, 0xce, 0x38, 0x80,
+        ]);
+        // Content light level 1000/400
+        stream.extend_from_slice(&[
+            0x00, 0x00, 0x00, 0x01, 0x06, 0x90, 0x04, 0x03,
+            0xe8, 0x01, 0x90, 0x80,
+        ]);
+        stream.extend_from_slice(&idr);
+        // Still within the same coded video sequence
+        stream.extend_from_slice(&aud);
+        // The next IDR starts a sequence without HDR metadata
+        stream.extend_from_slice(&aud);
+        stream.extend_from_slice(&idr);
+
+        let mut parser = AnnexBParser::new();
+        parser.push(&stream);
+        let cll: Vec<_> = parser
+            .drain()
+            .map(|au| au.unwrap().hdr_metadata.content_light_level_info)
+            .collect();
+        assert_eq!(cll.len(), 3);
+        assert_eq!(cll[0].map(|c| c.max_content_light_level), Some(1000));
+        assert_eq!(cll[1], cll[0]);
+        assert_eq!(cll[2], None);
+    }
+}
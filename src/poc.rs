@@ -0,0 +1,339 @@
+use crate::nal::NalUnitType;
+use crate::slice::SliceHeader;
+use crate::sps::Sps;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PicOrderCnt {
+    pub top_field_order_cnt: Option<i32>,
+    pub bottom_field_order_cnt: Option<i32>,
+    pub pic_order_cnt: i32,
+}
+
+impl PicOrderCnt {
+    fn from_fields(header: &SliceHeader, top: i64, bottom: i64) -> Self {
+        if !header.field_pic_flag {
+            Self::new(Some(top), Some(bottom))
+        } else if header.bottom_field_flag {
+            Self::new(None, Some(bottom))
+        } else {
+            Self::new(Some(top), None)
+        }
+    }
+
+    fn new(top: Option<i64>, bottom: Option<i64>) -> Self {
+        let top_field_order_cnt = top.map(|v| v as i32);
+        let bottom_field_order_cnt = bottom.map(|v| v as i32);
+        let pic_order_cnt = match (top_field_order_cnt, bottom_field_order_cnt) {
+            (Some(t), Some(b)) => t.min(b),
+            (Some(t), None) => t,
+            (None, Some(b)) => b,
+            (None, None) => 0,
+        };
+
+        PicOrderCnt {
+            top_field_order_cnt,
+            bottom_field_order_cnt,
+            pic_order_cnt,
+        }
+    }
+
+    fn rebased(&self) -> Self {
+        let temp = self.pic_order_cnt as i64;
+        Self::new(
+            self.top_field_order_cnt.map(|v| v as i64 - temp),
+            self.bottom_field_order_cnt.map(|v| v as i64 - temp),
+        )
+    }
+}
+
+/// Picture order count decoding process (8.2.1), carrying the
+/// prevPicOrderCntMsb/Lsb, prevFrameNum and prevFrameNumOffset state
+/// between pictures.
+#[derive(Debug, Clone, Default)]
+pub struct PocCalculator {
+    prev_pic_order_cnt_msb: i64,
+    prev_pic_order_cnt_lsb: i64,
+    prev_frame_num_offset: i64,
+    prev_frame_num: u32,
+}
+
+impl PocCalculator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn compute(
+        &mut self,
+        header: &SliceHeader,
+        nal_type: NalUnitType,
+        nal_ref_idc: u8,
+        sps: &Sps,
+    ) -> PicOrderCnt {
+        let idr = nal_type == NalUnitType::IdrSlice;
+        let has_mmco5 = header
+            .dec_ref_pic_marking
+            .as_ref()
+            .is_some_and(|m| m.has_mmco5());
+
+        let poc = match sps.pic_order_cnt_type {
+            0 => self.compute_type0(header, idr, nal_ref_idc, has_mmco5, sps),
+            1 => self.compute_type1(header, idr, nal_ref_idc, has_mmco5, sps),
+            _ => self.compute_type2(header, idr, nal_ref_idc, has_mmco5, sps),
+        };
+
+        // A picture with memory_management_control_operation 5 has its order
+        // counts rebased after decoding so the smaller one becomes zero
+        if has_mmco5 {
+            poc.rebased()
+        } else {
+            poc
+        }
+    }
+
+    // 8.2.1.1
+    fn compute_type0(
+        &mut self,
+        header: &SliceHeader,
+        idr: bool,
+        nal_ref_idc: u8,
+        has_mmco5: bool,
+        sps: &Sps,
+    ) -> PicOrderCnt {
+        if idr {
+            self.prev_pic_order_cnt_msb = 0;
+            self.prev_pic_order_cnt_lsb = 0;
+        }
+
+        let max_pic_order_cnt_lsb = 1i64 << (sps.log2_max_pic_order_cnt_lsb_minus4 + 4);
+        let lsb = header.pic_order_cnt_lsb as i64;
+        let prev_lsb = self.prev_pic_order_cnt_lsb;
+        let prev_msb = self.prev_pic_order_cnt_msb;
+
+        let msb = if lsb < prev_lsb && (prev_lsb - lsb) >= max_pic_order_cnt_lsb / 2 {
+            prev_msb + max_pic_order_cnt_lsb
+        } else if lsb > prev_lsb && (lsb - prev_lsb) > max_pic_order_cnt_lsb / 2 {
+            prev_msb - max_pic_order_cnt_lsb
+        } else {
+            prev_msb
+        };
+
+        let top = msb + lsb;
+        let bottom = if header.field_pic_flag {
+            msb + lsb
+        } else {
+            top + header.delta_pic_order_cnt_bottom as i64
+        };
+        let poc = PicOrderCnt::from_fields(header, top, bottom);
+
+        if nal_ref_idc != 0 {
+            if has_mmco5 {
+                self.prev_pic_order_cnt_msb = 0;
+                self.prev_pic_order_cnt_lsb = if header.field_pic_flag && header.bottom_field_flag {
+                    0
+                } else {
+                    poc.rebased().top_field_order_cnt.unwrap_or(0) as i64
+                };
+            } else {
+                self.prev_pic_order_cnt_msb = msb;
+                self.prev_pic_order_cnt_lsb = lsb;
+            }
+        }
+
+        poc
+    }
+
+    // 8.2.1.2
+    fn compute_type1(
+        &mut self,
+        header: &SliceHeader,
+        idr: bool,
+        nal_ref_idc: u8,
+        has_mmco5: bool,
+        sps: &Sps,
+    ) -> PicOrderCnt {
+        let frame_num_offset = self.frame_num_offset(header, idr, has_mmco5, sps);
+
+        let cycle_len = sps.offset_for_ref_frame.len() as i64;
+        let mut abs_frame_num = if cycle_len != 0 {
+            frame_num_offset + header.frame_num as i64
+        } else {
+            0
+        };
+        if nal_ref_idc == 0 && abs_frame_num > 0 {
+            abs_frame_num -= 1;
+        }
+
+        let mut expected_pic_order_cnt = 0i64;
+        if abs_frame_num > 0 {
+            let pic_order_cnt_cycle_cnt = (abs_frame_num - 1) / cycle_len;
+            let frame_num_in_pic_order_cnt_cycle = (abs_frame_num - 1) % cycle_len;
+            let expected_delta_per_pic_order_cnt_cycle: i64 =
+                sps.offset_for_ref_frame.iter().map(|&v| v as i64).sum();
+
+            expected_pic_order_cnt = pic_order_cnt_cycle_cnt * expected_delta_per_pic_order_cnt_cycle;
+            for &offset in &sps.offset_for_ref_frame[..=frame_num_in_pic_order_cnt_cycle as usize] {
+                expected_pic_order_cnt += offset as i64;
+            }
+        }
+        if nal_ref_idc == 0 {
+            expected_pic_order_cnt += sps.offset_for_non_ref_pic as i64;
+        }
+
+        let delta = [
+            header.delta_pic_order_cnt[0] as i64,
+            header.delta_pic_order_cnt[1] as i64,
+        ];
+        let top_to_bottom = sps.offset_for_top_to_bottom_field as i64;
+
+        let top = expected_pic_order_cnt + delta[0];
+        let bottom = if header.field_pic_flag {
+            expected_pic_order_cnt + top_to_bottom + delta[0]
+        } else {
+            top + top_to_bottom + delta[1]
+        };
+
+        PicOrderCnt::from_fields(header, top, bottom)
+    }
+
+    // 8.2.1.3
+    fn compute_type2(
+        &mut self,
+        header: &SliceHeader,
+        idr: bool,
+        nal_ref_idc: u8,
+        has_mmco5: bool,
+        sps: &Sps,
+    ) -> PicOrderCnt {
+        let frame_num_offset = self.frame_num_offset(header, idr, has_mmco5, sps);
+
+        let temp_pic_order_cnt = if idr {
+            0
+        } else if nal_ref_idc == 0 {
+            2 * (frame_num_offset + header.frame_num as i64) - 1
+        } else {
+            2 * (frame_num_offset + header.frame_num as i64)
+        };
+
+        PicOrderCnt::from_fields(header, temp_pic_order_cnt, temp_pic_order_cnt)
+    }
+
+    // FrameNumOffset shared by POC types 1 and 2. Updates prevFrameNum and
+    // prevFrameNumOffset for the next picture, treating a picture with
+    // memory_management_control_operation 5 as having frame_num 0.
+    fn frame_num_offset(&mut self, header: &SliceHeader, idr: bool, has_mmco5: bool, sps: &Sps) -> i64 {
+        let max_frame_num = 1i64 << (sps.log2_max_frame_num_minus4 + 4);
+
+        let frame_num_offset = if idr {
+            0
+        } else if self.prev_frame_num > header.frame_num {
+            self.prev_frame_num_offset + max_frame_num
+        } else {
+            self.prev_frame_num_offset
+        };
+
+        if has_mmco5 {
+            self.prev_frame_num_offset = 0;
+            self.prev_frame_num = 0;
+        } else {
+            self.prev_frame_num_offset = frame_num_offset;
+            self.prev_frame_num = header.frame_num;
+        }
+
+        frame_num_offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slice::{test_slice_header, DecRefPicMarking, MemoryManagementControlOperation};
+    use crate::sps::test_sps;
+
+    fn header(frame_num: u32, pic_order_cnt_lsb: u32) -> SliceHeader {
+        SliceHeader {
+            pic_order_cnt_lsb,
+            ..test_slice_header(frame_num)
+        }
+    }
+
+    #[test]
+    fn test_poc_type0_lsb_wrap() {
+        let sps = test_sps();
+        let mut calc = PocCalculator::new();
+
+        // MaxPicOrderCntLsb = 16
+        let pocs: Vec<i32> = [(0, 0, true), (1, 4, false), (2, 12, false), (3, 2, false), (4, 10, false)]
+            .iter()
+            .map(|&(frame_num, lsb, idr)| {
+                let nal_type = if idr { NalUnitType::IdrSlice } else { NalUnitType::NonIdrSlice };
+                calc.compute(&header(frame_num, lsb), nal_type, 1, &sps).pic_order_cnt
+            })
+            .collect();
+
+        assert_eq!(pocs, vec![0, 4, 12, 18, 26]);
+    }
+
+    #[test]
+    fn test_poc_type0_bottom_delta_and_non_ref() {
+        let sps = test_sps();
+        let mut calc = PocCalculator::new();
+
+        let mut idr = header(0, 0);
+        idr.delta_pic_order_cnt_bottom = 1;
+        let poc = calc.compute(&idr, NalUnitType::IdrSlice, 3, &sps);
+        assert_eq!(poc.top_field_order_cnt, Some(0));
+        assert_eq!(poc.bottom_field_order_cnt, Some(1));
+
+        // Non-reference pictures do not update prevPicOrderCntMsb/Lsb
+        assert_eq!(calc.compute(&header(1, 14), NalUnitType::NonIdrSlice, 0, &sps).pic_order_cnt, -2);
+        assert_eq!(calc.compute(&header(1, 6), NalUnitType::NonIdrSlice, 1, &sps).pic_order_cnt, 6);
+    }
+
+    #[test]
+    fn test_poc_type0_mmco5_resets() {
+        let sps = test_sps();
+        let mut calc = PocCalculator::new();
+
+        calc.compute(&header(0, 0), NalUnitType::IdrSlice, 3, &sps);
+        calc.compute(&header(1, 8), NalUnitType::NonIdrSlice, 1, &sps);
+
+        let mut reset = header(2, 12);
+        reset.dec_ref_pic_marking = Some(DecRefPicMarking {
+            adaptive_ref_pic_marking_mode_flag: true,
+            mmcos: vec![MemoryManagementControlOperation::MarkAllUnused],
+            ..Default::default()
+        });
+        assert_eq!(calc.compute(&reset, NalUnitType::NonIdrSlice, 1, &sps).pic_order_cnt, 0);
+        assert_eq!(calc.compute(&header(1, 4), NalUnitType::NonIdrSlice, 1, &sps).pic_order_cnt, 4);
+    }
+
+    #[test]
+    fn test_poc_type1_cycle() {
+        let mut sps = Sps { pic_order_cnt_type: 1, ..test_sps() };
+        sps.offset_for_ref_frame = vec![2];
+        sps.num_ref_frames_in_pic_order_cnt_cycle = 1;
+        sps.offset_for_non_ref_pic = -1;
+        let mut calc = PocCalculator::new();
+
+        assert_eq!(calc.compute(&header(0, 0), NalUnitType::IdrSlice, 3, &sps).pic_order_cnt, 0);
+        assert_eq!(calc.compute(&header(1, 0), NalUnitType::NonIdrSlice, 1, &sps).pic_order_cnt, 2);
+        assert_eq!(calc.compute(&header(2, 0), NalUnitType::NonIdrSlice, 1, &sps).pic_order_cnt, 4);
+        assert_eq!(calc.compute(&header(3, 0), NalUnitType::NonIdrSlice, 0, &sps).pic_order_cnt, 3);
+    }
+
+    #[test]
+    fn test_poc_type2_frame_num_wrap() {
+        let sps = Sps { pic_order_cnt_type: 2, ..test_sps() };
+        let mut calc = PocCalculator::new();
+
+        assert_eq!(calc.compute(&header(0, 0), NalUnitType::IdrSlice, 3, &sps).pic_order_cnt, 0);
+        assert_eq!(calc.compute(&header(15, 0), NalUnitType::NonIdrSlice, 1, &sps).pic_order_cnt, 30);
+        // frame_num wraps at MaxFrameNum = 16
+        assert_eq!(calc.compute(&header(0, 0), NalUnitType::NonIdrSlice, 1, &sps).pic_order_cnt, 32);
+        assert_eq!(calc.compute(&header(1, 0), NalUnitType::NonIdrSlice, 0, &sps).pic_order_cnt, 33);
+    }
+}
@@ -11,6 +11,8 @@ pub struct Pps {
     
     pub num_slice_groups_minus1: u32,
     pub slice_group_map_type: u32,
+    pub slice_group_change_direction_flag: bool,
+    pub slice_group_change_rate_minus1: u32,
     
     pub num_ref_idx_l0_default_active_minus1: u8,
     pub num_ref_idx_l1_default_active_minus1: u8,
@@ -26,6 +28,9 @@ pub struct Pps {
     pub transform_8x8_mode_flag: bool,
     pub pic_scaling_matrix_present_flag: bool,
     pub second_chroma_qp_index_offset: i8,
+    
+    /// The RBSP this PPS was parsed from, kept for re-muxing.
+    pub rbsp: Vec<u8>,
 }
 
 impl Pps {
@@ -47,6 +52,8 @@ impl Pps {
         
         let num_slice_groups_minus1 = read_ue(&mut reader)?;
         let mut slice_group_map_type = 0;
+        let mut slice_group_change_direction_flag = false;
+        let mut slice_group_change_rate_minus1 = 0;
         
         if num_slice_groups_minus1 > 0 {
             slice_group_map_type = read_ue(&mut reader)?;
@@ -63,9 +70,9 @@ impl Pps {
                         let _bottom_right = read_ue(&mut reader)?;
                     }
                 }
-                3 | 4 | 5 => {
-                    let _slice_group_change_direction_flag = reader.read_flag()?;
-                    let _slice_group_change_rate_minus1 = read_ue(&mut reader)?;
+                3..=5 => {
+                    slice_group_change_direction_flag = reader.read_flag()?;
+                    slice_group_change_rate_minus1 = read_ue(&mut reader)?;
                 }
                 6 => {
                     let pic_size_in_map_units_minus1 = read_ue(&mut reader)?;
@@ -92,17 +99,17 @@ impl Pps {
         let weighted_bipred_idc = reader.read_bits(2)? as u8;
         
         let pic_init_qp_minus26 = read_se(&mut reader)?;
-        if pic_init_qp_minus26 < -26 || pic_init_qp_minus26 > 25 {
+        if !(-26..=25).contains(&pic_init_qp_minus26) {
             return Err(Error::MalformedPps("Invalid pic_init_qp".into()));
         }
         
         let pic_init_qs_minus26 = read_se(&mut reader)?;
-        if pic_init_qs_minus26 < -26 || pic_init_qs_minus26 > 25 {
+        if !(-26..=25).contains(&pic_init_qs_minus26) {
             return Err(Error::MalformedPps("Invalid pic_init_qs".into()));
         }
         
         let chroma_qp_index_offset = read_se(&mut reader)?;
-        if chroma_qp_index_offset < -12 || chroma_qp_index_offset > 12 {
+        if !(-12..=12).contains(&chroma_qp_index_offset) {
             return Err(Error::MalformedPps("Invalid chroma_qp_index_offset".into()));
         }
         
@@ -130,7 +137,7 @@ impl Pps {
             }
             
             second_chroma_qp_index_offset = read_se(&mut reader)?;
-            if second_chroma_qp_index_offset < -12 || second_chroma_qp_index_offset > 12 {
+            if !(-12..=12).contains(&second_chroma_qp_index_offset) {
                 return Err(Error::MalformedPps("Invalid second_chroma_qp_index_offset".into()));
             }
         }
@@ -142,6 +149,8 @@ impl Pps {
             bottom_field_pic_order_in_frame_present_flag,
             num_slice_groups_minus1,
             slice_group_map_type,
+            slice_group_change_direction_flag,
+            slice_group_change_rate_minus1,
             num_ref_idx_l0_default_active_minus1: num_ref_idx_l0_default_active_minus1 as u8,
             num_ref_idx_l1_default_active_minus1: num_ref_idx_l1_default_active_minus1 as u8,
             weighted_pred_flag,
@@ -155,6 +164,7 @@ impl Pps {
             transform_8x8_mode_flag,
             pic_scaling_matrix_present_flag,
             second_chroma_qp_index_offset: second_chroma_qp_index_offset as i8,
+            rbsp: rbsp.to_vec(),
         })
     }
 }
@@ -0,0 +1,331 @@
+use crate::au::AccessUnit;
+use crate::nal::NalUnitType;
+use std::collections::VecDeque;
+
+/// Decode and presentation positions assigned by the [`ReorderBuffer`],
+/// counted in pictures. `dts` is shifted back by the reorder depth so that
+/// `pts >= dts` always holds, matching how MP4 composition offsets are built.
+/// When a new SPS deepens the buffer both are moved forwards to keep `dts`
+/// increasing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputTiming {
+    pub decode_index: u64,
+    pub output_index: u64,
+    pub dts: i64,
+    pub pts: i64,
+}
+
+impl OutputTiming {
+    pub fn composition_offset(&self) -> i64 {
+        self.pts - self.dts
+    }
+}
+
+/// Buffers access units in decoding order and releases them in output
+/// (picture order count) order. The buffer holds at most
+/// `Sps::num_reorder_frames()` pictures and is flushed at IDR pictures and
+/// pictures carrying memory_management_control_operation 5, where the
+/// picture order count restarts. Access units without a picture keep their
+/// place behind the pictures decoded before them.
+#[derive(Debug, Default)]
+pub struct ReorderBuffer {
+    pending: Vec<(Option<i32>, AccessUnit)>,
+    ready: VecDeque<AccessUnit>,
+    decode_index: u64,
+    output_index: u64,
+    reorder_depth: u64,
+    timestamp_offset: i64,
+}
+
+impl ReorderBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, mut au: AccessUnit) {
+        let Some(poc) = au.pic_order_cnt() else {
+            if self.pending.is_empty() {
+                self.ready.push_back(au);
+            } else {
+                self.pending.push((None, au));
+            }
+            return;
+        };
+
+        if starts_new_poc_period(&au) {
+            self.flush();
+        }
+
+        if let Some(sps) = &au.sps {
+            if self.pending.is_empty() {
+                let field_pic = au.slice_header.as_ref().is_some_and(|h| h.field_pic_flag);
+                let depth = sps.num_reorder_frames() as u64 * if field_pic { 2 } else { 1 };
+                self.set_reorder_depth(depth);
+            }
+        }
+
+        au.output_timing = Some(OutputTiming {
+            decode_index: self.decode_index,
+            output_index: 0,
+            dts: self.decode_index as i64 - self.reorder_depth as i64 + self.timestamp_offset,
+            pts: 0,
+        });
+        self.decode_index += 1;
+
+        self.pending.push((Some(poc), au));
+
+        while self.buffered_pictures() > self.reorder_depth {
+            self.output_next();
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<AccessUnit> {
+        self.ready.pop_front()
+    }
+
+    /// Releases every buffered picture, e.g. at end of stream.
+    pub fn flush(&mut self) {
+        while !self.pending.is_empty() {
+            self.output_next();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len() + self.ready.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty() && self.ready.is_empty()
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Changes the reorder depth while no pictures are buffered. Deepening
+    /// the buffer mid-stream would move dts backwards, so both timestamps
+    /// are shifted forwards instead.
+    fn set_reorder_depth(&mut self, depth: u64) {
+        if self.decode_index > 0 {
+            self.timestamp_offset += depth.saturating_sub(self.reorder_depth) as i64;
+        }
+        self.reorder_depth = depth;
+    }
+
+    fn buffered_pictures(&self) -> u64 {
+        self.pending.iter().filter(|(poc, _)| poc.is_some()).count() as u64
+    }
+
+    fn output_next(&mut self) {
+        let next = self
+            .pending
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, (poc, _))| poc.map(|poc| (idx, poc)))
+            .min_by_key(|(_, poc)| *poc)
+            .map(|(idx, _)| idx);
+
+        if let Some(idx) = next {
+            let (_, mut au) = self.pending.remove(idx);
+            if let Some(timing) = au.output_timing.as_mut() {
+                timing.output_index = self.output_index;
+                timing.pts = self.output_index as i64 + self.timestamp_offset;
+            }
+            self.output_index += 1;
+            self.ready.push_back(au);
+        }
+
+        // Release the access units without a picture once every picture
+        // decoded before them is out
+        let released = self
+            .pending
+            .iter()
+            .position(|(poc, _)| poc.is_some())
+            .unwrap_or(self.pending.len());
+        self.ready.extend(self.pending.drain(..released).map(|(_, au)| au));
+    }
+}
+
+fn starts_new_poc_period(au: &AccessUnit) -> bool {
+    let idr = au
+        .first_vcl_nal()
+        .is_some_and(|nal| nal.nal_type == NalUnitType::IdrSlice);
+
+    let mmco5 = au
+        .slice_header
+        .as_ref()
+        .and_then(|h| h.dec_ref_pic_marking.as_ref())
+        .is_some_and(|m| m.has_mmco5());
+
+    idr || mmco5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitreader::{pack_bit_string, BitReader};
+    use crate::eg::write_ue;
+    use crate::nal::Nal;
+    use crate::poc::PicOrderCnt;
+    use crate::sps::Sps;
+    use crate::vui::Vui;
+    use std::sync::Arc;
+
+    fn test_sps(max_num_reorder_frames: u32) -> Arc<Sps> {
+        // VUI carrying only the bitstream restriction
+        let mut bits = String::from("00000000 11");
+        for value in [0, 0, 16, 16, max_num_reorder_frames, max_num_reorder_frames] {
+            bits.extend(write_ue(value).iter().map(|&b| if b { '1' } else { '0' }));
+        }
+        bits.push('1');
+        let data = pack_bit_string(&bits);
+        let vui = Vui::parse(&mut BitReader::new(&data)).unwrap();
+        Arc::new(Sps { vui: Some(vui), ..crate::sps::test_sps() })
+    }
+
+    fn picture(nal_type: NalUnitType, poc: i32, sps: &Arc<Sps>) -> AccessUnit {
+        let mut au = AccessUnit::new();
+        au.add_nal(Nal {
+            start_code_len: 4,
+            ref_idc: 1,
+            nal_type,
+            ebsp: vec![0x88],
+        });
+        au.sps = Some(sps.clone());
+        au.poc = Some(PicOrderCnt {
+            top_field_order_cnt: Some(poc),
+            bottom_field_order_cnt: Some(poc),
+            pic_order_cnt: poc,
+        });
+        au
+    }
+
+    fn drain(buffer: &mut ReorderBuffer) -> Vec<(i32, OutputTiming)> {
+        std::iter::from_fn(|| buffer.pop())
+            .map(|au| (au.pic_order_cnt().unwrap(), au.output_timing.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_reorders_b_frames() {
+        let sps = test_sps(1);
+        let mut buffer = ReorderBuffer::new();
+
+        // Decode order I0 P4 B2 P8 B6
+        buffer.push(picture(NalUnitType::IdrSlice, 0, &sps));
+        for poc in [4, 2, 8, 6] {
+            buffer.push(picture(NalUnitType::NonIdrSlice, poc, &sps));
+        }
+        buffer.flush();
+
+        let out = drain(&mut buffer);
+        let pocs: Vec<i32> = out.iter().map(|(poc, _)| *poc).collect();
+        assert_eq!(pocs, vec![0, 2, 4, 6, 8]);
+
+        for (_, timing) in &out {
+            assert_eq!(timing.pts, timing.output_index as i64);
+            assert!(timing.composition_offset() >= 0);
+        }
+        // P4 is decoded second but shown third
+        assert_eq!(out[2].1.decode_index, 1);
+        assert_eq!(out[2].1.dts, 0);
+        assert_eq!(out[2].1.composition_offset(), 2);
+    }
+
+    #[test]
+    fn test_flushes_on_idr() {
+        let sps = test_sps(2);
+        let mut buffer = ReorderBuffer::new();
+
+        buffer.push(picture(NalUnitType::IdrSlice, 0, &sps));
+        buffer.push(picture(NalUnitType::NonIdrSlice, 8, &sps));
+        assert!(buffer.pop().is_none());
+
+        // A new IDR restarts POC at 0 but must not overtake the earlier pictures
+        buffer.push(picture(NalUnitType::IdrSlice, 0, &sps));
+        let pocs: Vec<i32> = drain(&mut buffer).iter().map(|(poc, _)| *poc).collect();
+        assert_eq!(pocs, vec![0, 8]);
+        assert_eq!(buffer.len(), 1);
+    }
+
+    #[test]
+    fn test_pictureless_access_units_keep_decode_order() {
+        let sps = test_sps(1);
+        let mut buffer = ReorderBuffer::new();
+
+        buffer.push(picture(NalUnitType::IdrSlice, 0, &sps));
+        buffer.push(picture(NalUnitType::NonIdrSlice, 4, &sps));
+        let mut parameter_sets = AccessUnit::new();
+        parameter_sets.add_nal(Nal {
+            start_code_len: 4,
+            ref_idc: 3,
+            nal_type: NalUnitType::Pps,
+            ebsp: vec![0xce],
+        });
+        buffer.push(parameter_sets);
+
+        // P4 stays buffered so B2 can still be shown before it, and the
+        // parameter sets wait behind P4
+        assert_eq!(buffer.pop().unwrap().pic_order_cnt(), Some(0));
+        assert!(buffer.pop().is_none());
+
+        buffer.push(picture(NalUnitType::NonIdrSlice, 2, &sps));
+        buffer.flush();
+        let pocs: Vec<_> = std::iter::from_fn(|| buffer.pop())
+            .map(|au| au.pic_order_cnt())
+            .collect();
+        assert_eq!(pocs, vec![Some(2), Some(4), None]);
+    }
+
+    #[test]
+    fn test_dts_stays_monotonic_across_sps_change() {
+        let shallow = test_sps(0);
+        let deep = test_sps(2);
+        let mut buffer = ReorderBuffer::new();
+
+        buffer.push(picture(NalUnitType::IdrSlice, 0, &shallow));
+        buffer.push(picture(NalUnitType::NonIdrSlice, 2, &shallow));
+        // Decode order I0 P4 B2 with a deeper buffer after the new IDR
+        buffer.push(picture(NalUnitType::IdrSlice, 0, &deep));
+        buffer.push(picture(NalUnitType::NonIdrSlice, 4, &deep));
+        buffer.push(picture(NalUnitType::NonIdrSlice, 2, &deep));
+        buffer.flush();
+
+        let out = drain(&mut buffer);
+        let mut by_decode_order: Vec<_> = out.iter().map(|(_, timing)| *timing).collect();
+        by_decode_order.sort_by_key(|timing| timing.decode_index);
+        assert!(by_decode_order.windows(2).all(|w| w[0].dts < w[1].dts));
+        assert!(out.windows(2).all(|w| w[0].1.pts < w[1].1.pts));
+        assert!(out.iter().all(|(_, timing)| timing.composition_offset() >= 0));
+    }
+
+    #[test]
+    fn test_captions_follow_presentation_order() {
+        use crate::sei::{SeiMessage, SeiPayload, UserDataRegistered};
+
+        let sps = test_sps(1);
+        let mut buffer = ReorderBuffer::new();
+
+        // Decode order I0 P4 B2, each carrying one CEA-608 pair tagged with its POC
+        for (nal_type, poc) in [(NalUnitType::IdrSlice, 0), (NalUnitType::NonIdrSlice, 4), (NalUnitType::NonIdrSlice, 2)] {
+            let mut au = picture(nal_type, poc, &sps);
+            let data = [
+                0xb5, 0x00, 0x31, 0x47, 0x41, 0x39, 0x34, 0x03,
+                0xc1, 0xff, 0xfc, poc as u8, 0x80, 0xff,
+            ];
+            au.sei_messages.push(SeiMessage {
+                payload_type: 4,
+                payload_size: data.len() as u32,
+                payload: SeiPayload::UserDataRegistered(UserDataRegistered::parse(&data).unwrap()),
+                raw_payload: None,
+            });
+            buffer.push(au);
+        }
+        buffer.flush();
+
+        let captions: Vec<u8> = std::iter::from_fn(|| buffer.pop())
+            .flat_map(|au| au.captions().map(|packet| packet.cc_data_1).collect::<Vec<_>>())
+            .collect();
+        assert_eq!(captions, vec![0, 2, 4]);
+    }
+}
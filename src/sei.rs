@@ -1,12 +1,56 @@
-use crate::Result;
+use crate::bitreader::BitReader;
+use crate::eg::{read_ue, write_ue};
+use crate::nal::{rbsp_to_ebsp, Nal, NalUnitType};
+use crate::sps::Sps;
+use crate::{Error, Result};
+
+mod atsc;
+mod display;
+mod encoder_info;
+mod film_grain;
+mod hdr;
+mod marking;
+mod misb;
+mod nesting;
+mod registry;
+mod timing;
+mod tone_mapping;
+
+pub use atsc::{
+    Afd, AtscUserData, BarData, CaptionPacket, CcData, CcType, UserDataRegistered,
+    COUNTRY_CODE_CHINA, COUNTRY_CODE_US, PROVIDER_CODE_ATSC, USER_IDENTIFIER_DTG1,
+    USER_IDENTIFIER_GA94,
+};
+pub use display::{
+    DisplayOrientation, FrameGridPositions, FramePacking, FramePackingArrangement, FramePackingType,
+    Orientation, PanScan, PanScanOffsets, PanScanRect,
+};
+pub use encoder_info::{EncoderInfo, UUID_X264};
+pub use film_grain::{
+    FilmGrainCharacteristics, FilmGrainColourDescription, FilmGrainModel, IntensityInterval,
+};
+pub use hdr::{
+    AmbientViewingEnvironment, ContentLightLevelInfo, HdrMetadata, MasteringDisplayColourVolume,
+};
+pub use marking::DecRefPicMarkingRepetition;
+pub use misb::{
+    KlvItem, KlvLocalSet, PrecisionTimeStamp, SMPTE_UL_PREFIX, UAS_DATALINK_LS_KEY,
+    UUID_MISB_PRECISION_TIME_STAMP,
+};
+pub use nesting::{
+    LayerRepresentation, MvcNestingTarget, MvcScalableNesting, NestedLayers, ScalableNesting,
+};
+pub use registry::{CustomSei, SeiHandlerKey, SeiHandlerRegistry};
+pub use timing::{BufferingPeriod, ClockTimestamp, InitialCpbRemovalDelay, PicStruct, PicTiming};
+pub use tone_mapping::{LuminanceDynamicRange, ToneMapModel, ToneMapping, ToneMappingInfo};
 
 #[derive(Debug, Clone)]
 pub enum SeiPayload {
-    BufferingPeriod,
-    PicTiming,
-    PanScanRect,
+    BufferingPeriod(BufferingPeriod),
+    PicTiming(PicTiming),
+    PanScanRect(PanScanRect),
     FillerPayload,
-    UserDataRegistered,
+    UserDataRegistered(UserDataRegistered),
     UserDataUnregistered(Vec<u8>),
     RecoveryPoint {
         recovery_frame_cnt: u32,
@@ -14,7 +58,7 @@ pub enum SeiPayload {
         broken_link_flag: bool,
         changing_slice_group_idc: u8,
     },
-    DecRefPicMarkingRepetition,
+    DecRefPicMarkingRepetition(DecRefPicMarkingRepetition),
     SparePic,
     SceneInfo,
     SubSeqInfo,
@@ -26,25 +70,25 @@ pub enum SeiPayload {
     ProgressiveRefinementSegmentStart,
     ProgressiveRefinementSegmentEnd,
     MotionConstrainedSliceGroupSet,
-    FilmGrainCharacteristics,
+    FilmGrainCharacteristics(FilmGrainCharacteristics),
     DeblockingFilterDisplayPreference,
     StereoVideoInfo,
     PostFilterHint,
-    ToneMappingInfo,
+    ToneMappingInfo(ToneMappingInfo),
     ScalabilityInfo,
     SubPicScalableLayer,
     NonRequiredLayerRep,
     PriorityLayerInfo,
     LayersNotPresent,
     LayerDependencyChange,
-    ScalableNesting,
+    ScalableNesting(ScalableNesting),
     BaseLayerTemporalHrd,
     QualityLayerIntegrityCheck,
     RedundantPicProperty,
     Tl0DepRepIndex,
     TlSwitchingPoint,
     ParallelDecodingInfo,
-    MvcScalableNesting,
+    MvcScalableNesting(MvcScalableNesting),
     ViewScalabilityInfo,
     MultiviewSceneInfo,
     MultiviewAcquisitionInfo,
@@ -52,9 +96,9 @@ pub enum SeiPayload {
     ViewDependencyChange,
     OperationPointsNotPresent,
     BaseViewTemporalHrd,
-    FramePackingArrangement,
+    FramePackingArrangement(FramePackingArrangement),
     MultiviewViewPosition,
-    DisplayOrientation,
+    DisplayOrientation(DisplayOrientation),
     MvcdScalableNesting,
     MvcdViewScalabilityInfo,
     DepthRepresentationInfo,
@@ -62,6 +106,12 @@ pub enum SeiPayload {
     DepthTiming,
     DepthSamplingInfo,
     ConstrainedDepthParameterSetIdentifier,
+    MasteringDisplayColourVolume(MasteringDisplayColourVolume),
+    ContentLightLevelInfo(ContentLightLevelInfo),
+    AlternativeTransferCharacteristics {
+        preferred_transfer_characteristics: u8,
+    },
+    AmbientViewingEnvironment(AmbientViewingEnvironment),
     Unknown(u32, Vec<u8>),
 }
 
@@ -70,93 +120,265 @@ pub struct SeiMessage {
     pub payload_type: u32,
     pub payload_size: u32,
     pub payload: SeiPayload,
+    /// The sei_payload bytes as read from the bitstream, `None` for
+    /// messages built in code.
+    pub raw_payload: Option<Vec<u8>>,
 }
 
 impl SeiMessage {
     pub fn parse(rbsp: &[u8]) -> Result<Vec<SeiMessage>> {
-        let mut messages = Vec::new();
-        let mut pos = 0;
-        
-        while pos < rbsp.len() && rbsp[pos] != 0x80 {
-            let mut payload_type = 0u32;
-            while pos < rbsp.len() && rbsp[pos] == 0xFF {
-                payload_type += 255;
-                pos += 1;
-            }
-            if pos < rbsp.len() {
-                payload_type += rbsp[pos] as u32;
-                pos += 1;
-            }
-            
-            let mut payload_size = 0u32;
-            while pos < rbsp.len() && rbsp[pos] == 0xFF {
-                payload_size += 255;
-                pos += 1;
+        Self::parse_with_sps(rbsp, None)
+    }
+
+    pub fn user_data_unregistered(uuid: [u8; 16], data: &[u8]) -> Self {
+        let mut payload = uuid.to_vec();
+        payload.extend_from_slice(data);
+        SeiMessage {
+            payload_type: 5,
+            payload_size: payload.len() as u32,
+            payload: SeiPayload::UserDataUnregistered(payload),
+            raw_payload: None,
+        }
+    }
+
+    pub fn user_data_registered(user_data: UserDataRegistered) -> Self {
+        SeiMessage {
+            payload_type: 4,
+            payload_size: user_data.to_bytes().len() as u32,
+            payload: SeiPayload::UserDataRegistered(user_data),
+            raw_payload: None,
+        }
+    }
+
+    /// The sei_payload bytes. Only user data (registered and unregistered),
+    /// recovery point, mastering display colour volume, content light
+    /// level, alternative transfer characteristics and ambient viewing
+    /// environment payloads, plus `Unknown`, are written from their fields.
+    /// Any other payload is copied from `raw_payload`, so edits to its
+    /// fields are not written, and fails when built in code without it.
+    pub fn payload_bytes(&self) -> Result<Vec<u8>> {
+        match &self.payload {
+            SeiPayload::UserDataUnregistered(data) | SeiPayload::Unknown(_, data) => {
+                Ok(data.clone())
             }
-            if pos < rbsp.len() {
-                payload_size += rbsp[pos] as u32;
-                pos += 1;
+            SeiPayload::UserDataRegistered(user_data) => Ok(user_data.to_bytes()),
+            SeiPayload::RecoveryPoint {
+                recovery_frame_cnt,
+                exact_match_flag,
+                broken_link_flag,
+                changing_slice_group_idc,
+            } => {
+                let mut bits = write_ue(*recovery_frame_cnt);
+                bits.extend([
+                    *exact_match_flag,
+                    *broken_link_flag,
+                    changing_slice_group_idc & 2 != 0,
+                    changing_slice_group_idc & 1 != 0,
+                ]);
+                Ok(pack_payload_bits(&bits))
             }
-            
-            let payload_end = (pos + payload_size as usize).min(rbsp.len());
-            let payload_data = &rbsp[pos..payload_end];
-            
-            let payload = match payload_type {
-                6 => parse_recovery_point(payload_data)?,
-                5 => {
-                    if payload_data.len() >= 16 {
-                        SeiPayload::UserDataUnregistered(payload_data.to_vec())
-                    } else {
-                        SeiPayload::Unknown(payload_type, payload_data.to_vec())
-                    }
-                }
-                _ => SeiPayload::Unknown(payload_type, payload_data.to_vec()),
-            };
+            SeiPayload::MasteringDisplayColourVolume(mdcv) => Ok(mdcv.to_bytes()),
+            SeiPayload::ContentLightLevelInfo(cll) => Ok(cll.to_bytes()),
+            SeiPayload::AlternativeTransferCharacteristics {
+                preferred_transfer_characteristics,
+            } => Ok(vec![*preferred_transfer_characteristics]),
+            SeiPayload::AmbientViewingEnvironment(ambient) => Ok(ambient.to_bytes()),
+            _ => self.raw_payload.clone().ok_or_else(|| {
+                Error::BitstreamError(format!(
+                    "Cannot serialize SEI payload type {} without its raw payload",
+                    self.payload_type
+                ))
+            }),
+        }
+    }
+
+    /// Builds an SEI RBSP (7.3.2.3) carrying `messages`, including the
+    /// rbsp_trailing_bits. The payload size written is the length of
+    /// [`SeiMessage::payload_bytes`]; `payload_size` is not consulted.
+    pub fn to_rbsp(messages: &[SeiMessage]) -> Result<Vec<u8>> {
+        let mut rbsp = Vec::new();
+
+        for message in messages {
+            let payload = message.payload_bytes()?;
+            write_ff_coded(&mut rbsp, message.payload_type);
+            write_ff_coded(&mut rbsp, payload.len() as u32);
+            rbsp.extend_from_slice(&payload);
+        }
+
+        rbsp.push(0x80);
+        Ok(rbsp)
+    }
+
+    /// Builds an SEI NAL unit carrying `messages`.
+    pub fn to_nal(messages: &[SeiMessage]) -> Result<Nal> {
+        Ok(Nal {
+            start_code_len: 3,
+            ref_idc: 0,
+            nal_type: NalUnitType::Sei,
+            ebsp: rbsp_to_ebsp(&Self::to_rbsp(messages)?),
+        })
+    }
+
+    /// Parses the messages of an SEI RBSP. Payloads whose syntax depends on
+    /// the active SPS (buffering period, picture timing, reference marking
+    /// repetition) are only decoded when `sps` is given and are reported as
+    /// `Unknown` otherwise. Payloads that fail to parse are also reported as
+    /// `Unknown`, so one bad payload does not hide the rest of the RBSP.
+    pub fn parse_with_sps(rbsp: &[u8], sps: Option<&Sps>) -> Result<Vec<SeiMessage>> {
+        Self::parse_messages(rbsp, sps, false)
+    }
+
+    /// Parses the sei_message()s inside a nesting SEI. Nesting messages may
+    /// not be nested again and are reported as `Unknown`.
+    pub(crate) fn parse_nested(data: &[u8], sps: Option<&Sps>) -> Result<Vec<SeiMessage>> {
+        Self::parse_messages(data, sps, true)
+    }
+
+    fn parse_messages(rbsp: &[u8], sps: Option<&Sps>, nested: bool) -> Result<Vec<SeiMessage>> {
+        let mut messages = Vec::new();
+
+        for (payload_type, payload_size, payload_data) in split_payloads(rbsp) {
+            // A payload that fails to parse must not hide the others
+            let payload = Self::parse_payload(payload_type, payload_data, sps, nested)
+                .unwrap_or_else(|_| SeiPayload::Unknown(payload_type, payload_data.to_vec()));
             
             messages.push(SeiMessage {
                 payload_type,
                 payload_size,
                 payload,
+                raw_payload: Some(payload_data.to_vec()),
             });
-            
-            pos = payload_end;
         }
         
         Ok(messages)
     }
+
+    fn parse_payload(
+        payload_type: u32,
+        payload_data: &[u8],
+        sps: Option<&Sps>,
+        nested: bool,
+    ) -> Result<SeiPayload> {
+        let payload = match (payload_type, sps) {
+            (0, Some(sps)) => {
+                SeiPayload::BufferingPeriod(BufferingPeriod::parse(payload_data, sps)?)
+            }
+            (1, Some(sps)) => SeiPayload::PicTiming(PicTiming::parse(payload_data, sps)?),
+            (2, _) => SeiPayload::PanScanRect(PanScanRect::parse(payload_data)?),
+            (6, _) => parse_recovery_point(payload_data)?,
+            (4, _) => {
+                SeiPayload::UserDataRegistered(UserDataRegistered::parse(payload_data)?)
+            }
+            (5, _) => {
+                if payload_data.len() >= 16 {
+                    SeiPayload::UserDataUnregistered(payload_data.to_vec())
+                } else {
+                    SeiPayload::Unknown(payload_type, payload_data.to_vec())
+                }
+            }
+            (7, Some(sps)) => SeiPayload::DecRefPicMarkingRepetition(
+                DecRefPicMarkingRepetition::parse(payload_data, sps)?,
+            ),
+            (19, _) => SeiPayload::FilmGrainCharacteristics(
+                FilmGrainCharacteristics::parse(payload_data)?,
+            ),
+            (23, _) => SeiPayload::ToneMappingInfo(ToneMappingInfo::parse(payload_data)?),
+            (30, _) if !nested => {
+                SeiPayload::ScalableNesting(ScalableNesting::parse(payload_data, sps)?)
+            }
+            (37, _) if !nested => {
+                SeiPayload::MvcScalableNesting(MvcScalableNesting::parse(payload_data, sps)?)
+            }
+            (45, _) => SeiPayload::FramePackingArrangement(
+                FramePackingArrangement::parse(payload_data)?,
+            ),
+            (47, _) => SeiPayload::DisplayOrientation(DisplayOrientation::parse(payload_data)?),
+            (137, _) => SeiPayload::MasteringDisplayColourVolume(
+                MasteringDisplayColourVolume::parse(payload_data)?,
+            ),
+            (144, _) => SeiPayload::ContentLightLevelInfo(
+                ContentLightLevelInfo::parse(payload_data)?,
+            ),
+            (147, _) => SeiPayload::AlternativeTransferCharacteristics {
+                preferred_transfer_characteristics: *payload_data
+                    .first()
+                    .ok_or(Error::UnexpectedEof)?,
+            },
+            (148, _) => SeiPayload::AmbientViewingEnvironment(
+                AmbientViewingEnvironment::parse(payload_data)?,
+            ),
+            _ => SeiPayload::Unknown(payload_type, payload_data.to_vec()),
+        };
+
+        Ok(payload)
+    }
 }
 
-fn parse_recovery_point(data: &[u8]) -> Result<SeiPayload> {
-    if data.is_empty() {
-        return Ok(SeiPayload::Unknown(6, data.to_vec()));
+/// Packs payload bits MSB first, ending them with bit_equal_to_one and
+/// zero bits up to the next byte boundary unless already aligned (D.1).
+fn pack_payload_bits(bits: &[bool]) -> Vec<u8> {
+    let mut bytes = vec![0u8; bits.len().div_ceil(8)];
+    for (i, _) in bits.iter().enumerate().filter(|(_, &bit)| bit) {
+        bytes[i / 8] |= 0x80 >> (i % 8);
+    }
+    if !bits.len().is_multiple_of(8) {
+        bytes[bits.len() / 8] |= 0x80 >> (bits.len() % 8);
+    }
+    bytes
+}
+
+fn write_ff_coded(out: &mut Vec<u8>, mut value: u32) {
+    while value >= 255 {
+        out.push(0xFF);
+        value -= 255;
     }
-    
-    let mut recovery_frame_cnt = 0u32;
+    out.push(value as u8);
+}
+
+/// Splits an SEI RBSP into `(payload_type, payload_size, payload)` triples.
+/// A payload running past the end of the RBSP is truncated.
+pub(crate) fn split_payloads(rbsp: &[u8]) -> Vec<(u32, u32, &[u8])> {
+    let mut payloads = Vec::new();
     let mut pos = 0;
-    
-    while pos < data.len() {
-        let byte = data[pos];
-        recovery_frame_cnt = (recovery_frame_cnt << 7) | ((byte & 0x7F) as u32);
-        pos += 1;
-        if (byte & 0x80) == 0 {
-            break;
+
+    while pos < rbsp.len() && rbsp[pos] != 0x80 {
+        let mut payload_type = 0u32;
+        while pos < rbsp.len() && rbsp[pos] == 0xFF {
+            payload_type += 255;
+            pos += 1;
         }
+        if pos < rbsp.len() {
+            payload_type += rbsp[pos] as u32;
+            pos += 1;
+        }
+
+        let mut payload_size = 0u32;
+        while pos < rbsp.len() && rbsp[pos] == 0xFF {
+            payload_size += 255;
+            pos += 1;
+        }
+        if pos < rbsp.len() {
+            payload_size += rbsp[pos] as u32;
+            pos += 1;
+        }
+
+        let payload_end = (pos + payload_size as usize).min(rbsp.len());
+        payloads.push((payload_type, payload_size, &rbsp[pos..payload_end]));
+        pos = payload_end;
     }
-    
-    let mut flags = 0u8;
-    if pos < data.len() {
-        flags = data[pos];
-    }
-    
-    let exact_match_flag = (flags & 0x80) != 0;
-    let broken_link_flag = (flags & 0x40) != 0;
-    let changing_slice_group_idc = (flags & 0x30) >> 4;
-    
+
+    payloads
+}
+
+/// Recovery point SEI (D.1.7).
+fn parse_recovery_point(data: &[u8]) -> Result<SeiPayload> {
+    let mut reader = BitReader::new(data);
+
     Ok(SeiPayload::RecoveryPoint {
-        recovery_frame_cnt,
-        exact_match_flag,
-        broken_link_flag,
-        changing_slice_group_idc,
+        recovery_frame_cnt: read_ue(&mut reader)?,
+        exact_match_flag: reader.read_flag()?,
+        broken_link_flag: reader.read_flag()?,
+        changing_slice_group_idc: reader.read_bits(2)? as u8,
     })
 }
 
@@ -174,21 +396,119 @@ mod tests {
     #[test]
     fn test_sei_parse_recovery_point() {
         let rbsp = vec![
+            0x06,
+            0x01,
+            0xa4,
             0x06,
             0x02,
-            0x00,
+            0x2c,
             0x40,
             0x80,
         ];
         
         let messages = SeiMessage::parse(&rbsp).unwrap();
-        assert_eq!(messages.len(), 1);
+        assert_eq!(messages.len(), 2);
         assert_eq!(messages[0].payload_type, 6);
         
-        if let SeiPayload::RecoveryPoint { recovery_frame_cnt, .. } = &messages[0].payload {
+        if let SeiPayload::RecoveryPoint { recovery_frame_cnt, broken_link_flag, .. } =
+            &messages[0].payload
+        {
             assert_eq!(*recovery_frame_cnt, 0);
+            assert!(*broken_link_flag);
         } else {
             panic!("Expected RecoveryPoint payload");
         }
+        assert!(matches!(
+            messages[1].payload,
+            SeiPayload::RecoveryPoint {
+                recovery_frame_cnt: 4,
+                exact_match_flag: true,
+                broken_link_flag: false,
+                changing_slice_group_idc: 0,
+            }
+        ));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_buffering_period_needs_sps() {
+        // seq_parameter_set_id 0 with no HRD signalled
+        let rbsp = vec![0x00, 0x01, 0x80, 0x80];
+        
+        let messages = SeiMessage::parse(&rbsp).unwrap();
+        assert!(matches!(messages[0].payload, SeiPayload::Unknown(0, _)));
+        
+        let sps = crate::sps::test_sps();
+        let messages = SeiMessage::parse_with_sps(&rbsp, Some(&sps)).unwrap();
+        match &messages[0].payload {
+            SeiPayload::BufferingPeriod(bp) => {
+                assert_eq!(bp.seq_parameter_set_id, 0);
+                assert!(bp.nal_initial_cpb_removal_delays.is_empty());
+            }
+            other => panic!("Expected BufferingPeriod payload, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_sei_serialization() {
+        let messages = [
+            SeiMessage::user_data_unregistered([0x11; 16], &[0x00, 0x00, 0x01]),
+            SeiMessage {
+                payload_type: 300,
+                payload_size: 255,
+                payload: SeiPayload::Unknown(300, vec![0x55; 255]),
+                raw_payload: None,
+            },
+        ];
+
+        let rbsp = SeiMessage::to_rbsp(&messages).unwrap();
+        assert_eq!(&rbsp[..2], &[0x05, 0x13]);
+        assert_eq!(&rbsp[21..25], &[0xff, 0x2d, 0xff, 0x00]);
+        assert_eq!(rbsp.len(), 25 + 255 + 1);
+        assert_eq!(rbsp.last(), Some(&0x80));
+
+        let parsed = SeiMessage::parse(&rbsp).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[1].payload_type, 300);
+        assert_eq!(parsed[1].payload_size, 255);
+        assert_eq!(parsed[0].payload_bytes().unwrap(), messages[0].payload_bytes().unwrap());
+
+        // The user data's 00 00 01 needs an emulation prevention byte
+        let nal = SeiMessage::to_nal(&messages[..1]).unwrap();
+        assert_eq!(nal.header_byte(), 0x06);
+        assert_eq!(&nal.ebsp[18..22], &[0x00, 0x00, 0x03, 0x01]);
+        assert_eq!(nal.to_rbsp(), SeiMessage::to_rbsp(&messages[..1]).unwrap());
+
+        let registered = SeiMessage::user_data_registered(UserDataRegistered {
+            country_code: 0xb5,
+            country_code_extension: None,
+            provider_code: Some(0x003c),
+            data: vec![0x01, 0x02],
+            atsc: None,
+        });
+        assert_eq!(registered.payload_size, 5);
+        assert_eq!(
+            SeiMessage::to_rbsp(&[registered]).unwrap(),
+            [0x04, 0x05, 0xb5, 0x00, 0x3c, 0x01, 0x02, 0x80]
+        );
+    }
+
+    #[test]
+    fn test_bad_payload_keeps_other_messages() {
+        let rbsp = vec![
+            // Buffering period naming SPS 1 while SPS 0 is active
+            0x00, 0x01, 0x40,
+            // Tone mapping with a target bit depth of 0
+            0x17, 0x03, 0xa1, 0x00, 0x00,
+            // Recovery point
+            0x06, 0x01, 0x84,
+            0x80,
+        ];
+
+        let sps = crate::sps::test_sps();
+        let messages = SeiMessage::parse_with_sps(&rbsp, Some(&sps)).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(messages[0].payload, SeiPayload::Unknown(0, ref data) if data == &[0x40]));
+        assert!(matches!(messages[1].payload, SeiPayload::Unknown(23, _)));
+        assert!(matches!(messages[2].payload, SeiPayload::RecoveryPoint { .. }));
+    }
+}
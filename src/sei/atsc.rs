@@ -0,0 +1,314 @@
+use crate::{Error, Result};
+
+pub const COUNTRY_CODE_US: u8 = 0xb5;
+/// T.35 country code for China, which also defines a terminal provider code.
+pub const COUNTRY_CODE_CHINA: u8 = 0x26;
+pub const PROVIDER_CODE_ATSC: u16 = 0x0031;
+/// ATSC_user_identifier for A/53 user data ("GA94").
+pub const USER_IDENTIFIER_GA94: u32 = 0x4741_3934;
+/// afd_identifier for Active Format Description ("DTG1").
+pub const USER_IDENTIFIER_DTG1: u32 = 0x4454_4731;
+
+const USER_DATA_TYPE_CC_DATA: u8 = 0x03;
+const USER_DATA_TYPE_BAR_DATA: u8 = 0x06;
+
+/// user_data_registered_itu_t_t35 SEI (D.1.6). The provider code is only
+/// read for countries known to define one (US and China); `data` holds
+/// everything after it, or after the country code otherwise. ATSC payloads
+/// are additionally decoded into `atsc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserDataRegistered {
+    pub country_code: u8,
+    pub country_code_extension: Option<u8>,
+    pub provider_code: Option<u16>,
+    pub data: Vec<u8>,
+    pub atsc: Option<AtscUserData>,
+}
+
+impl UserDataRegistered {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let (&country_code, mut rest) = data
+            .split_first()
+            .ok_or_else(|| Error::BitstreamError("Empty user_data_registered_itu_t_t35".into()))?;
+
+        let mut country_code_extension = None;
+        if country_code == 0xff {
+            let (&ext, tail) = rest.split_first().ok_or(Error::UnexpectedEof)?;
+            country_code_extension = Some(ext);
+            rest = tail;
+        }
+
+        let mut provider_code = None;
+        if matches!(country_code, COUNTRY_CODE_US | COUNTRY_CODE_CHINA) && rest.len() >= 2 {
+            provider_code = Some(u16::from_be_bytes([rest[0], rest[1]]));
+            rest = &rest[2..];
+        }
+
+        let atsc = if country_code == COUNTRY_CODE_US && provider_code == Some(PROVIDER_CODE_ATSC) {
+            AtscUserData::parse(rest).ok()
+        } else {
+            None
+        };
+
+        Ok(UserDataRegistered {
+            country_code,
+            country_code_extension,
+            provider_code,
+            data: rest.to_vec(),
+            atsc,
+        })
+    }
+
+    /// The payload bytes, rebuilt from the codes and `data`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.country_code];
+        bytes.extend(self.country_code_extension);
+        if let Some(provider_code) = self.provider_code {
+            bytes.extend_from_slice(&provider_code.to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AtscUserData {
+    CcData(CcData),
+    BarData(BarData),
+    Afd(Afd),
+    Unknown { user_identifier: u32, data: Vec<u8> },
+}
+
+impl AtscUserData {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 4 {
+            return Err(Error::UnexpectedEof);
+        }
+        let user_identifier = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let rest = &data[4..];
+
+        match (user_identifier, rest.first()) {
+            (USER_IDENTIFIER_GA94, Some(&USER_DATA_TYPE_CC_DATA)) => Ok(AtscUserData::CcData(CcData::parse(&rest[1..])?)),
+            (USER_IDENTIFIER_GA94, Some(&USER_DATA_TYPE_BAR_DATA)) => Ok(AtscUserData::BarData(BarData::parse(&rest[1..])?)),
+            (USER_IDENTIFIER_DTG1, _) => Ok(AtscUserData::Afd(Afd::parse(rest)?)),
+            _ => Ok(AtscUserData::Unknown {
+                user_identifier,
+                data: rest.to_vec(),
+            }),
+        }
+    }
+}
+
+/// cc_type of a caption packet (CEA-708 4.4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CcType {
+    Ntsc608Field1,
+    Ntsc608Field2,
+    DtvccPacketData,
+    DtvccPacketStart,
+}
+
+impl CcType {
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            Self::Ntsc608Field1 => 0,
+            Self::Ntsc608Field2 => 1,
+            Self::DtvccPacketData => 2,
+            Self::DtvccPacketStart => 3,
+        }
+    }
+}
+
+impl From<u8> for CcType {
+    fn from(value: u8) -> Self {
+        match value & 0x03 {
+            0 => Self::Ntsc608Field1,
+            1 => Self::Ntsc608Field2,
+            2 => Self::DtvccPacketData,
+            _ => Self::DtvccPacketStart,
+        }
+    }
+}
+
+/// One cc_data triplet: a CEA-608 byte pair or two bytes of a CEA-708
+/// DTVCC packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptionPacket {
+    pub cc_valid: bool,
+    pub cc_type: CcType,
+    pub cc_data_1: u8,
+    pub cc_data_2: u8,
+}
+
+/// ATSC A/53 Part 4 cc_data().
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CcData {
+    pub process_em_data_flag: bool,
+    pub process_cc_data_flag: bool,
+    pub additional_data_flag: bool,
+    pub em_data: u8,
+    pub packets: Vec<CaptionPacket>,
+}
+
+impl CcData {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 2 {
+            return Err(Error::UnexpectedEof);
+        }
+
+        let flags = data[0];
+        let cc_count = (flags & 0x1f) as usize;
+        let em_data = data[1];
+
+        let triplets = data
+            .get(2..2 + cc_count * 3)
+            .ok_or_else(|| Error::BitstreamError("cc_data shorter than cc_count".into()))?;
+        let packets = triplets
+            .chunks_exact(3)
+            .map(|t| CaptionPacket {
+                cc_valid: t[0] & 0x04 != 0,
+                cc_type: CcType::from(t[0]),
+                cc_data_1: t[1],
+                cc_data_2: t[2],
+            })
+            .collect();
+
+        Ok(CcData {
+            process_em_data_flag: flags & 0x80 != 0,
+            process_cc_data_flag: flags & 0x40 != 0,
+            additional_data_flag: flags & 0x20 != 0,
+            em_data,
+            packets,
+        })
+    }
+}
+
+/// Active Format Description (ETSI TS 101 154 Annex B). `active_format` is
+/// the 4-bit AFD code, `None` when active_format_flag is 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Afd {
+    pub active_format: Option<u8>,
+}
+
+impl Afd {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let &flags = data.first().ok_or(Error::UnexpectedEof)?;
+
+        let active_format = if flags & 0x40 != 0 {
+            Some(data.get(1).ok_or(Error::UnexpectedEof)? & 0x0f)
+        } else {
+            None
+        };
+
+        Ok(Afd { active_format })
+    }
+}
+
+/// ATSC A/53 Part 4 bar_data(). Each bar is `None` when its flag is 0;
+/// top/bottom are luma line numbers, left/right luma sample numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarData {
+    pub line_number_end_of_top_bar: Option<u16>,
+    pub line_number_start_of_bottom_bar: Option<u16>,
+    pub pixel_number_end_of_left_bar: Option<u16>,
+    pub pixel_number_start_of_right_bar: Option<u16>,
+}
+
+impl BarData {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let &flags = data.first().ok_or(Error::UnexpectedEof)?;
+        let mut values = data[1..]
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) & 0x3fff);
+
+        let mut bar = |bit: u8| -> Result<Option<u16>> {
+            if flags & bit != 0 {
+                values.next().map(Some).ok_or(Error::UnexpectedEof)
+            } else {
+                Ok(None)
+            }
+        };
+
+        Ok(BarData {
+            line_number_end_of_top_bar: bar(0x80)?,
+            line_number_start_of_bottom_bar: bar(0x40)?,
+            pixel_number_end_of_left_bar: bar(0x20)?,
+            pixel_number_start_of_right_bar: bar(0x10)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ga94_cc_data() {
+        let data = vec![
+            0xb5, 0x00, 0x31, 0x47, 0x41, 0x39, 0x34, 0x03,
+            0xc2, 0xff, 0xfc, 0x94, 0x20, 0xff, 0x80, 0x80,
+            0xff,
+        ];
+        let user_data = UserDataRegistered::parse(&data).unwrap();
+
+        assert_eq!(user_data.country_code, COUNTRY_CODE_US);
+        assert_eq!(user_data.provider_code, Some(PROVIDER_CODE_ATSC));
+
+        let Some(AtscUserData::CcData(cc_data)) = user_data.atsc else {
+            panic!("Expected cc_data, got {:?}", user_data.atsc);
+        };
+        assert!(cc_data.process_cc_data_flag);
+        assert_eq!(
+            cc_data.packets,
+            vec![
+                CaptionPacket { cc_valid: true, cc_type: CcType::Ntsc608Field1, cc_data_1: 0x94, cc_data_2: 0x20 },
+                CaptionPacket { cc_valid: true, cc_type: CcType::DtvccPacketStart, cc_data_1: 0x80, cc_data_2: 0x80 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_other_providers_kept_raw() {
+        let user_data = UserDataRegistered::parse(&[0xff, 0x01, 0x12, 0x34, 0xaa]).unwrap();
+        assert_eq!(user_data.country_code_extension, Some(0x01));
+        assert_eq!(user_data.provider_code, None);
+        assert_eq!(user_data.data, vec![0x12, 0x34, 0xaa]);
+        assert!(user_data.atsc.is_none());
+        assert_eq!(user_data.to_bytes(), [0xff, 0x01, 0x12, 0x34, 0xaa]);
+
+        let user_data = UserDataRegistered::parse(&[0x26, 0x00, 0x04, 0xaa]).unwrap();
+        assert_eq!(user_data.provider_code, Some(0x0004));
+        assert_eq!(user_data.data, vec![0xaa]);
+
+        // cc_count says 2 but only one triplet follows
+        let data = [0xb5, 0x00, 0x31, 0x47, 0x41, 0x39, 0x34, 0x03, 0xc2, 0xff, 0xfc, 0x94, 0x20];
+        assert!(UserDataRegistered::parse(&data).unwrap().atsc.is_none());
+    }
+
+    #[test]
+    fn test_afd_and_bar_data() {
+        let afd = [0xb5, 0x00, 0x31, 0x44, 0x54, 0x47, 0x31, 0x41, 0xfa];
+        let user_data = UserDataRegistered::parse(&afd).unwrap();
+        assert_eq!(user_data.atsc, Some(AtscUserData::Afd(Afd { active_format: Some(10) })));
+
+        assert_eq!(Afd::parse(&[0x01]).unwrap(), Afd { active_format: None });
+
+        // Letterbox: top bar ends at line 59, bottom bar starts at line 420
+        let bars = [
+            0xb5, 0x00, 0x31, 0x47, 0x41, 0x39, 0x34, 0x06,
+            0xcf, 0xc0, 0x3b, 0xc1, 0xa4,
+        ];
+        let user_data = UserDataRegistered::parse(&bars).unwrap();
+        assert_eq!(
+            user_data.atsc,
+            Some(AtscUserData::BarData(BarData {
+                line_number_end_of_top_bar: Some(59),
+                line_number_start_of_bottom_bar: Some(420),
+                pixel_number_end_of_left_bar: None,
+                pixel_number_start_of_right_bar: None,
+            }))
+        );
+
+        assert!(BarData::parse(&[0xcf, 0xc0, 0x3b]).is_err());
+    }
+}
@@ -0,0 +1,327 @@
+use crate::bitreader::BitReader;
+use crate::eg::{read_se, read_ue};
+use crate::{Error, Result};
+
+/// Rectangle offsets in units of 1/16 luma sample relative to the
+/// cropped frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PanScanOffsets {
+    pub pan_scan_rect_left_offset: i32,
+    pub pan_scan_rect_right_offset: i32,
+    pub pan_scan_rect_top_offset: i32,
+    pub pan_scan_rect_bottom_offset: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanScan {
+    /// pan_scan_cnt_minus1 + 1 rectangles, one per field or frame of the
+    /// picture.
+    pub rects: Vec<PanScanOffsets>,
+    pub pan_scan_rect_repetition_period: u32,
+}
+
+/// Pan-scan rectangle SEI (D.1.5). `pan_scan` is `None` when the message
+/// cancels an earlier one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanScanRect {
+    pub pan_scan_rect_id: u32,
+    pub pan_scan: Option<PanScan>,
+}
+
+impl PanScanRect {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = BitReader::new(data);
+
+        let pan_scan_rect_id = read_ue(&mut reader)?;
+        if reader.read_flag()? {
+            return Ok(PanScanRect { pan_scan_rect_id, pan_scan: None });
+        }
+
+        let pan_scan_cnt_minus1 = read_ue(&mut reader)?;
+        if pan_scan_cnt_minus1 > 2 {
+            return Err(Error::BitstreamError(format!(
+                "Invalid pan_scan_cnt_minus1 {}",
+                pan_scan_cnt_minus1
+            )));
+        }
+
+        let rects = (0..=pan_scan_cnt_minus1)
+            .map(|_| {
+                Ok(PanScanOffsets {
+                    pan_scan_rect_left_offset: read_se(&mut reader)?,
+                    pan_scan_rect_right_offset: read_se(&mut reader)?,
+                    pan_scan_rect_top_offset: read_se(&mut reader)?,
+                    pan_scan_rect_bottom_offset: read_se(&mut reader)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(PanScanRect {
+            pan_scan_rect_id,
+            pan_scan: Some(PanScan {
+                rects,
+                pan_scan_rect_repetition_period: read_ue(&mut reader)?,
+            }),
+        })
+    }
+}
+
+/// frame_packing_arrangement_type values from Table D-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePackingType {
+    Checkerboard,
+    ColumnInterleaved,
+    RowInterleaved,
+    SideBySide,
+    TopBottom,
+    TemporalInterleaved,
+    Reserved(u8),
+}
+
+impl FramePackingType {
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            Self::Checkerboard => 0,
+            Self::ColumnInterleaved => 1,
+            Self::RowInterleaved => 2,
+            Self::SideBySide => 3,
+            Self::TopBottom => 4,
+            Self::TemporalInterleaved => 5,
+            Self::Reserved(v) => *v,
+        }
+    }
+}
+
+impl From<u8> for FramePackingType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Checkerboard,
+            1 => Self::ColumnInterleaved,
+            2 => Self::RowInterleaved,
+            3 => Self::SideBySide,
+            4 => Self::TopBottom,
+            5 => Self::TemporalInterleaved,
+            _ => Self::Reserved(value),
+        }
+    }
+}
+
+/// Sample grid positions of the two constituent frames, in units of 1/16
+/// luma sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameGridPositions {
+    pub frame0_grid_position_x: u8,
+    pub frame0_grid_position_y: u8,
+    pub frame1_grid_position_x: u8,
+    pub frame1_grid_position_y: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramePacking {
+    pub frame_packing_arrangement_type: FramePackingType,
+    pub quincunx_sampling_flag: bool,
+    pub content_interpretation_type: u8,
+    pub spatial_flipping_flag: bool,
+    pub frame0_flipped_flag: bool,
+    pub field_views_flag: bool,
+    pub current_frame_is_frame0_flag: bool,
+    pub frame0_self_contained_flag: bool,
+    pub frame1_self_contained_flag: bool,
+    /// Absent for quincunx sampling and temporal interleaving.
+    pub grid_positions: Option<FrameGridPositions>,
+    pub frame_packing_arrangement_repetition_period: u32,
+}
+
+/// Frame packing arrangement SEI (D.1.26). `packing` is `None` when the
+/// message cancels an earlier arrangement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramePackingArrangement {
+    pub frame_packing_arrangement_id: u32,
+    pub packing: Option<FramePacking>,
+}
+
+impl FramePackingArrangement {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = BitReader::new(data);
+
+        let frame_packing_arrangement_id = read_ue(&mut reader)?;
+        let cancel_flag = reader.read_flag()?;
+
+        let packing = if cancel_flag {
+            None
+        } else {
+            let frame_packing_arrangement_type = FramePackingType::from(reader.read_bits(7)? as u8);
+            let quincunx_sampling_flag = reader.read_flag()?;
+            let content_interpretation_type = reader.read_bits(6)? as u8;
+            let spatial_flipping_flag = reader.read_flag()?;
+            let frame0_flipped_flag = reader.read_flag()?;
+            let field_views_flag = reader.read_flag()?;
+            let current_frame_is_frame0_flag = reader.read_flag()?;
+            let frame0_self_contained_flag = reader.read_flag()?;
+            let frame1_self_contained_flag = reader.read_flag()?;
+
+            let grid_positions = if !quincunx_sampling_flag
+                && frame_packing_arrangement_type != FramePackingType::TemporalInterleaved
+            {
+                Some(FrameGridPositions {
+                    frame0_grid_position_x: reader.read_bits(4)? as u8,
+                    frame0_grid_position_y: reader.read_bits(4)? as u8,
+                    frame1_grid_position_x: reader.read_bits(4)? as u8,
+                    frame1_grid_position_y: reader.read_bits(4)? as u8,
+                })
+            } else {
+                None
+            };
+
+            let _frame_packing_arrangement_reserved_byte = reader.read_u8()?;
+            let frame_packing_arrangement_repetition_period = read_ue(&mut reader)?;
+
+            Some(FramePacking {
+                frame_packing_arrangement_type,
+                quincunx_sampling_flag,
+                content_interpretation_type,
+                spatial_flipping_flag,
+                frame0_flipped_flag,
+                field_views_flag,
+                current_frame_is_frame0_flag,
+                frame0_self_contained_flag,
+                frame1_self_contained_flag,
+                grid_positions,
+                frame_packing_arrangement_repetition_period,
+            })
+        };
+
+        Ok(FramePackingArrangement {
+            frame_packing_arrangement_id,
+            packing,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Orientation {
+    pub hor_flip: bool,
+    pub ver_flip: bool,
+    /// Anticlockwise rotation in units of 2^-16 of a full turn.
+    pub anticlockwise_rotation: u16,
+    pub display_orientation_repetition_period: u32,
+}
+
+impl Orientation {
+    pub fn rotation_degrees(&self) -> f64 {
+        self.anticlockwise_rotation as f64 * 360.0 / 65_536.0
+    }
+}
+
+/// Display orientation SEI (D.1.27). `orientation` is `None` when the
+/// message cancels an earlier orientation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayOrientation {
+    pub orientation: Option<Orientation>,
+}
+
+impl DisplayOrientation {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = BitReader::new(data);
+
+        let orientation = if reader.read_flag()? {
+            None
+        } else {
+            Some(Orientation {
+                hor_flip: reader.read_flag()?,
+                ver_flip: reader.read_flag()?,
+                anticlockwise_rotation: reader.read_u16()?,
+                display_orientation_repetition_period: read_ue(&mut reader)?,
+            })
+        };
+
+        Ok(DisplayOrientation { orientation })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitreader::pack_bit_string;
+
+    #[test]
+    fn test_side_by_side_frame_packing() {
+        let data = pack_bit_string(concat!(
+            "010 0", // id 1, not cancelled
+            "0000011 0 000001", // side-by-side, left view is frame 0
+            "0 0 0 1 0 0",
+            "1000 0000 0100 0000", // grid positions
+            "00000000 1", // reserved byte, repetition period 0
+            "0 1",
+        ));
+        let fpa = FramePackingArrangement::parse(&data).unwrap();
+        assert_eq!(fpa.frame_packing_arrangement_id, 1);
+
+        let packing = fpa.packing.unwrap();
+        assert_eq!(packing.frame_packing_arrangement_type, FramePackingType::SideBySide);
+        assert_eq!(packing.content_interpretation_type, 1);
+        assert!(packing.current_frame_is_frame0_flag);
+        assert_eq!(
+            packing.grid_positions,
+            Some(FrameGridPositions {
+                frame0_grid_position_x: 8,
+                frame0_grid_position_y: 0,
+                frame1_grid_position_x: 4,
+                frame1_grid_position_y: 0,
+            })
+        );
+        assert_eq!(packing.frame_packing_arrangement_repetition_period, 0);
+
+        // Temporal interleaving has no grid positions
+        let data = pack_bit_string("1 0 0000101 0 000000 000000 00000000 011 0 1");
+        let packing = FramePackingArrangement::parse(&data).unwrap().packing.unwrap();
+        assert_eq!(packing.frame_packing_arrangement_type, FramePackingType::TemporalInterleaved);
+        assert_eq!(packing.grid_positions, None);
+        assert_eq!(packing.frame_packing_arrangement_repetition_period, 2);
+
+        let cancel = FramePackingArrangement::parse(&pack_bit_string("1 1 0 1")).unwrap();
+        assert_eq!(cancel.packing, None);
+    }
+
+    #[test]
+    fn test_pan_scan_rect() {
+        // 4:3 centre cut of a 1920 wide frame: 240 luma samples each side
+        let data = pack_bit_string(concat!(
+            "1 0 1", // id 0, one rectangle
+            "0000000000001111000000000 0000000000001111000000001 1 1", // +3840, -3840, 0, 0
+            "011", // repetition period 2
+        ));
+        let rect = PanScanRect::parse(&data).unwrap();
+        assert_eq!(rect.pan_scan_rect_id, 0);
+        assert_eq!(
+            rect.pan_scan,
+            Some(PanScan {
+                rects: vec![PanScanOffsets {
+                    pan_scan_rect_left_offset: 3840,
+                    pan_scan_rect_right_offset: -3840,
+                    pan_scan_rect_top_offset: 0,
+                    pan_scan_rect_bottom_offset: 0,
+                }],
+                pan_scan_rect_repetition_period: 2,
+            })
+        );
+
+        assert_eq!(PanScanRect::parse(&pack_bit_string("1 1")).unwrap().pan_scan, None);
+        // pan_scan_cnt_minus1 of 3
+        assert!(PanScanRect::parse(&pack_bit_string("1 0 00100")).is_err());
+    }
+
+    #[test]
+    fn test_display_orientation() {
+        // Horizontal flip, rotated 90 degrees anticlockwise, persists
+        let data = pack_bit_string("0 1 0 0100000000000000 010 0 1");
+        let orientation = DisplayOrientation::parse(&data).unwrap().orientation.unwrap();
+        assert!(orientation.hor_flip);
+        assert!(!orientation.ver_flip);
+        assert_eq!(orientation.rotation_degrees(), 90.0);
+        assert_eq!(orientation.display_orientation_repetition_period, 1);
+
+        let cancel = DisplayOrientation::parse(&pack_bit_string("1 1")).unwrap();
+        assert_eq!(cancel.orientation, None);
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+
+/// uuid_iso_iec_11578 of the user_data_unregistered SEI x264 writes into
+/// the first access unit.
+pub const UUID_X264: [u8; 16] = [
+    0xdc, 0x45, 0xe9, 0xbd, 0xe6, 0xd9, 0x48, 0xb7,
+    0x96, 0x2c, 0xd8, 0x20, 0xd9, 0x23, 0xee, 0xef,
+];
+
+/// Encoder name, version and settings from an x264 info SEI, e.g.
+/// `x264 - core 164 r3095 baf4d4b - H.264/MPEG-4 AVC codec - ... - options:
+/// cabac=1 ref=3 ...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncoderInfo {
+    pub encoder: String,
+    /// Everything between the encoder name and the codec description.
+    pub version: String,
+    pub core: Option<u32>,
+    /// Options without a value, such as x265's resolution, map to "".
+    pub options: BTreeMap<String, String>,
+    pub info: String,
+}
+
+impl EncoderInfo {
+    /// Parses a user_data_unregistered payload, returning `None` unless it
+    /// carries the x264 UUID and a text body.
+    pub fn from_user_data_unregistered(payload: &[u8]) -> Option<Self> {
+        let data = payload.strip_prefix(&UUID_X264[..])?;
+        let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        Self::parse(&String::from_utf8_lossy(&data[..end]))
+    }
+
+    pub fn parse(info: &str) -> Option<Self> {
+        let mut sections = info.split(" - ");
+        let encoder = sections.next()?.trim();
+        if encoder.is_empty() {
+            return None;
+        }
+
+        let version = sections.next().unwrap_or_default().trim();
+        let core = version
+            .strip_prefix("core ")
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|core| core.parse().ok());
+
+        let options = info
+            .split_once("options:")
+            .map(|(_, list)| {
+                list.split_whitespace()
+                    .map(|option| match option.split_once('=') {
+                        Some((key, value)) => (key.to_string(), value.to_string()),
+                        None => (option.to_string(), String::new()),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(EncoderInfo {
+            encoder: encoder.to_string(),
+            version: version.to_string(),
+            core,
+            options,
+            info: info.to_string(),
+        })
+    }
+
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options.get(key).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X264_INFO: &str = "x264 - core 164 r3095 baf4d4b - H.264/MPEG-4 AVC codec - \
+        Copyleft 2003-2022 - http://www.videolan.org/x264.html - options: cabac=1 ref=3 \
+        deblock=1:0:0 analyse=0x3:0x113 me=hex subme=7 rc=crf crf=23.0";
+
+    #[test]
+    fn test_x264_info() {
+        let mut payload = UUID_X264.to_vec();
+        payload.extend_from_slice(X264_INFO.as_bytes());
+        payload.push(0);
+
+        let info = EncoderInfo::from_user_data_unregistered(&payload).unwrap();
+        assert_eq!(info.encoder, "x264");
+        assert_eq!(info.version, "core 164 r3095 baf4d4b");
+        assert_eq!(info.core, Some(164));
+        assert_eq!(info.options.len(), 8);
+        assert_eq!(info.option("deblock"), Some("1:0:0"));
+        assert_eq!(info.option("crf"), Some("23.0"));
+        assert_eq!(info.option("bframes"), None);
+        assert_eq!(info.info, X264_INFO);
+
+        payload[0] ^= 1;
+        assert!(EncoderInfo::from_user_data_unregistered(&payload).is_none());
+    }
+
+    #[test]
+    fn test_options_without_values() {
+        let info = EncoderInfo::parse("x265 (build 199) - 3.5 - options: 1920x1080 fps=25/1")
+            .unwrap();
+        assert_eq!(info.encoder, "x265 (build 199)");
+        assert_eq!(info.core, None);
+        assert_eq!(info.option("1920x1080"), Some(""));
+        assert_eq!(info.option("fps"), Some("25/1"));
+
+        assert!(EncoderInfo::parse("").is_none());
+    }
+}
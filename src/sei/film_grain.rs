@@ -0,0 +1,186 @@
+use crate::bitreader::BitReader;
+use crate::eg::{read_se, read_ue};
+use crate::Result;
+
+/// Colour description of the film grain model when it differs from the
+/// one signalled in the VUI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilmGrainColourDescription {
+    pub film_grain_bit_depth_luma_minus8: u8,
+    pub film_grain_bit_depth_chroma_minus8: u8,
+    pub film_grain_full_range_flag: bool,
+    pub film_grain_colour_primaries: u8,
+    pub film_grain_transfer_characteristics: u8,
+    pub film_grain_matrix_coefficients: u8,
+}
+
+/// One intensity interval of a colour component with its model values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntensityInterval {
+    pub intensity_interval_lower_bound: u8,
+    pub intensity_interval_upper_bound: u8,
+    pub comp_model_value: Vec<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilmGrainModel {
+    /// 0 for frequency filtering, 1 for auto-regression.
+    pub film_grain_model_id: u8,
+    pub colour_description: Option<FilmGrainColourDescription>,
+    /// 0 for additive, 1 for multiplicative blending.
+    pub blending_mode_id: u8,
+    pub log2_scale_factor: u8,
+    /// Intensity intervals per colour component (Y, Cb, Cr), `None` when
+    /// comp_model_present_flag is 0 for that component.
+    pub components: [Option<Vec<IntensityInterval>>; 3],
+    pub film_grain_characteristics_repetition_period: u32,
+}
+
+/// Film grain characteristics SEI (D.1.21). `model` is `None` when the
+/// message cancels an earlier one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilmGrainCharacteristics {
+    pub model: Option<FilmGrainModel>,
+}
+
+impl FilmGrainCharacteristics {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = BitReader::new(data);
+
+        if reader.read_flag()? {
+            return Ok(FilmGrainCharacteristics { model: None });
+        }
+
+        let film_grain_model_id = reader.read_bits(2)? as u8;
+
+        let colour_description = if reader.read_flag()? {
+            Some(FilmGrainColourDescription {
+                film_grain_bit_depth_luma_minus8: reader.read_bits(3)? as u8,
+                film_grain_bit_depth_chroma_minus8: reader.read_bits(3)? as u8,
+                film_grain_full_range_flag: reader.read_flag()?,
+                film_grain_colour_primaries: reader.read_u8()?,
+                film_grain_transfer_characteristics: reader.read_u8()?,
+                film_grain_matrix_coefficients: reader.read_u8()?,
+            })
+        } else {
+            None
+        };
+
+        let blending_mode_id = reader.read_bits(2)? as u8;
+        let log2_scale_factor = reader.read_bits(4)? as u8;
+
+        let mut comp_model_present_flag = [false; 3];
+        for flag in comp_model_present_flag.iter_mut() {
+            *flag = reader.read_flag()?;
+        }
+
+        let mut components: [Option<Vec<IntensityInterval>>; 3] = [None, None, None];
+        for (component, present) in components.iter_mut().zip(comp_model_present_flag) {
+            if !present {
+                continue;
+            }
+
+            let num_intensity_intervals = reader.read_u8()? as usize + 1;
+            let num_model_values = reader.read_bits(3)? as usize + 1;
+
+            let mut intervals = Vec::with_capacity(num_intensity_intervals);
+            for _ in 0..num_intensity_intervals {
+                let intensity_interval_lower_bound = reader.read_u8()?;
+                let intensity_interval_upper_bound = reader.read_u8()?;
+                let comp_model_value = (0..num_model_values)
+                    .map(|_| read_se(&mut reader))
+                    .collect::<Result<Vec<_>>>()?;
+
+                intervals.push(IntensityInterval {
+                    intensity_interval_lower_bound,
+                    intensity_interval_upper_bound,
+                    comp_model_value,
+                });
+            }
+            *component = Some(intervals);
+        }
+
+        let film_grain_characteristics_repetition_period = read_ue(&mut reader)?;
+
+        Ok(FilmGrainCharacteristics {
+            model: Some(FilmGrainModel {
+                film_grain_model_id,
+                colour_description,
+                blending_mode_id,
+                log2_scale_factor,
+                components,
+                film_grain_characteristics_repetition_period,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitreader::pack_bit_string;
+
+    #[test]
+    fn test_frequency_filtering_luma_only() {
+        let data = pack_bit_string(concat!(
+            "0 00 0", // frequency filtering, no separate colour description
+            "00 0011", // additive blending, log2_scale_factor 3
+            "1 0 0", // luma model only
+            "00000001 001", // two intervals of two values each
+            "00000000 01111111 00100 00101", // [0, 127]: 2, -2
+            "10000000 11111111 1 0001000", // [128, 255]: 0, 4
+            "010", // repetition period 1
+        ));
+        let fgc = FilmGrainCharacteristics::parse(&data).unwrap();
+        let model = fgc.model.unwrap();
+
+        assert_eq!(model.film_grain_model_id, 0);
+        assert_eq!(model.colour_description, None);
+        assert_eq!(model.log2_scale_factor, 3);
+        assert_eq!(model.film_grain_characteristics_repetition_period, 1);
+        assert!(model.components[1].is_none() && model.components[2].is_none());
+
+        let luma = model.components[0].as_ref().unwrap();
+        assert_eq!(
+            luma,
+            &vec![
+                IntensityInterval {
+                    intensity_interval_lower_bound: 0,
+                    intensity_interval_upper_bound: 127,
+                    comp_model_value: vec![2, -2],
+                },
+                IntensityInterval {
+                    intensity_interval_lower_bound: 128,
+                    intensity_interval_upper_bound: 255,
+                    comp_model_value: vec![0, 4],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_colour_description_and_cancel() {
+        let data = pack_bit_string(concat!(
+            "0 01 1 010 010 0 00001001 00010000 00001001", // 10-bit BT.2020 PQ
+            "01 0000 0 0 0 1",
+        ));
+        let model = FilmGrainCharacteristics::parse(&data).unwrap().model.unwrap();
+        assert_eq!(model.film_grain_model_id, 1);
+        assert_eq!(model.blending_mode_id, 1);
+        assert_eq!(
+            model.colour_description,
+            Some(FilmGrainColourDescription {
+                film_grain_bit_depth_luma_minus8: 2,
+                film_grain_bit_depth_chroma_minus8: 2,
+                film_grain_full_range_flag: false,
+                film_grain_colour_primaries: 9,
+                film_grain_transfer_characteristics: 16,
+                film_grain_matrix_coefficients: 9,
+            })
+        );
+        assert_eq!(model.components, [None, None, None]);
+
+        let cancel = FilmGrainCharacteristics::parse(&[0x80]).unwrap();
+        assert_eq!(cancel.model, None);
+    }
+}
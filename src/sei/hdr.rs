@@ -0,0 +1,214 @@
+use crate::bitreader::BitReader;
+use crate::Result;
+
+use super::{SeiMessage, SeiPayload};
+
+/// Mastering display colour volume SEI (D.1.29). Chromaticities are in
+/// units of 0.00002 and luminances in units of 0.0001 cd/m², in G, B, R
+/// order for the primaries as in SMPTE ST 2086.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MasteringDisplayColourVolume {
+    pub display_primaries_x: [u16; 3],
+    pub display_primaries_y: [u16; 3],
+    pub white_point_x: u16,
+    pub white_point_y: u16,
+    pub max_display_mastering_luminance: u32,
+    pub min_display_mastering_luminance: u32,
+}
+
+impl MasteringDisplayColourVolume {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = BitReader::new(data);
+
+        let mut display_primaries_x = [0; 3];
+        let mut display_primaries_y = [0; 3];
+        for c in 0..3 {
+            display_primaries_x[c] = reader.read_u16()?;
+            display_primaries_y[c] = reader.read_u16()?;
+        }
+
+        Ok(MasteringDisplayColourVolume {
+            display_primaries_x,
+            display_primaries_y,
+            white_point_x: reader.read_u16()?,
+            white_point_y: reader.read_u16()?,
+            max_display_mastering_luminance: reader.read_bits(32)?,
+            min_display_mastering_luminance: reader.read_bits(32)?,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(24);
+        for c in 0..3 {
+            bytes.extend_from_slice(&self.display_primaries_x[c].to_be_bytes());
+            bytes.extend_from_slice(&self.display_primaries_y[c].to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.white_point_x.to_be_bytes());
+        bytes.extend_from_slice(&self.white_point_y.to_be_bytes());
+        bytes.extend_from_slice(&self.max_display_mastering_luminance.to_be_bytes());
+        bytes.extend_from_slice(&self.min_display_mastering_luminance.to_be_bytes());
+        bytes
+    }
+
+    pub fn max_luminance_nits(&self) -> f64 {
+        self.max_display_mastering_luminance as f64 / 10_000.0
+    }
+
+    pub fn min_luminance_nits(&self) -> f64 {
+        self.min_display_mastering_luminance as f64 / 10_000.0
+    }
+}
+
+/// Content light level information SEI (D.1.31), in cd/m².
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLightLevelInfo {
+    pub max_content_light_level: u16,
+    pub max_pic_average_light_level: u16,
+}
+
+impl ContentLightLevelInfo {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = BitReader::new(data);
+
+        Ok(ContentLightLevelInfo {
+            max_content_light_level: reader.read_u16()?,
+            max_pic_average_light_level: reader.read_u16()?,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.max_content_light_level.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.max_pic_average_light_level.to_be_bytes());
+        bytes
+    }
+}
+
+/// Ambient viewing environment SEI (D.1.33). Illuminance is in units of
+/// 0.0001 lux and chromaticity in units of 0.00002.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmbientViewingEnvironment {
+    pub ambient_illuminance: u32,
+    pub ambient_light_x: u16,
+    pub ambient_light_y: u16,
+}
+
+impl AmbientViewingEnvironment {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = BitReader::new(data);
+
+        Ok(AmbientViewingEnvironment {
+            ambient_illuminance: reader.read_bits(32)?,
+            ambient_light_x: reader.read_u16()?,
+            ambient_light_y: reader.read_u16()?,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.ambient_illuminance.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.ambient_light_x.to_be_bytes());
+        bytes.extend_from_slice(&self.ambient_light_y.to_be_bytes());
+        bytes
+    }
+}
+
+/// The most recent HDR static metadata seen in the stream. Each field keeps
+/// its last value until a later SEI message replaces it or the coded video
+/// sequence ends (D.2), see [`HdrMetadata::reset`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HdrMetadata {
+    pub mastering_display_colour_volume: Option<MasteringDisplayColourVolume>,
+    pub content_light_level_info: Option<ContentLightLevelInfo>,
+    pub preferred_transfer_characteristics: Option<u8>,
+    pub ambient_viewing_environment: Option<AmbientViewingEnvironment>,
+}
+
+impl HdrMetadata {
+    pub fn update(&mut self, messages: &[SeiMessage]) {
+        for msg in messages {
+            match &msg.payload {
+                SeiPayload::MasteringDisplayColourVolume(mdcv) => {
+                    self.mastering_display_colour_volume = Some(*mdcv);
+                }
+                SeiPayload::ContentLightLevelInfo(cll) => {
+                    self.content_light_level_info = Some(*cll);
+                }
+                SeiPayload::AlternativeTransferCharacteristics {
+                    preferred_transfer_characteristics: value,
+                } => {
+                    self.preferred_transfer_characteristics = Some(*value);
+                }
+                SeiPayload::AmbientViewingEnvironment(ambient) => {
+                    self.ambient_viewing_environment = Some(*ambient);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Forgets all tracked values, e.g. when an IDR picture starts a new
+    /// coded video sequence.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hdr10_metadata() {
+        // BT.2020 primaries, D65, 1000/0.005 nits, MaxCLL 1000, MaxFALL 400
+        let rbsp = vec![
+            0x89, 0x18,
+            0x21, 0x34, 0x9b, 0xaa, 0x19, 0x96, 0x08, 0xfc,
+            0x8a, 0x48, 0x39, 0x08, 0x3d, 0x13, 0x40, 0x42,
+            0x00, 0x98, 0x96, 0x80, 0x00, 0x00, 0x00, 0x32,
+            0x90, 0x04, 0x03, 0xe8, 0x01, 0x90,
+            0x93, 0x01, 0x12,
+            0x80,
+        ];
+        let messages = SeiMessage::parse(&rbsp).unwrap();
+        assert_eq!(messages.len(), 3);
+        let written: Vec<_> = messages
+            .iter()
+            .map(|msg| SeiMessage { raw_payload: None, ..msg.clone() })
+            .collect();
+        assert_eq!(SeiMessage::to_rbsp(&written).unwrap(), rbsp);
+
+        let mut hdr = HdrMetadata::default();
+        assert!(hdr.is_empty());
+        hdr.update(&messages);
+
+        let mdcv = hdr.mastering_display_colour_volume.unwrap();
+        assert_eq!(mdcv.display_primaries_x, [8500, 6550, 35400]);
+        assert_eq!(mdcv.display_primaries_y, [39850, 2300, 14600]);
+        assert_eq!((mdcv.white_point_x, mdcv.white_point_y), (15635, 16450));
+        assert_eq!(mdcv.max_luminance_nits(), 1000.0);
+        assert_eq!(mdcv.min_luminance_nits(), 0.005);
+
+        assert_eq!(
+            hdr.content_light_level_info,
+            Some(ContentLightLevelInfo {
+                max_content_light_level: 1000,
+                max_pic_average_light_level: 400
+            })
+        );
+        // HLG
+        assert_eq!(hdr.preferred_transfer_characteristics, Some(18));
+        assert_eq!(hdr.ambient_viewing_environment, None);
+
+        let ambient =
+            AmbientViewingEnvironment::parse(&[0x00, 0x4c, 0x4b, 0x40, 0x3d, 0x13, 0x40, 0x42])
+                .unwrap();
+        assert_eq!(ambient.ambient_illuminance, 5_000_000);
+        assert_eq!(ambient.to_bytes(), [0x00, 0x4c, 0x4b, 0x40, 0x3d, 0x13, 0x40, 0x42]);
+        assert_eq!(mdcv.to_bytes(), &rbsp[2..26]);
+        assert_eq!(hdr.content_light_level_info.unwrap().to_bytes(), &rbsp[28..32]);
+        assert!(MasteringDisplayColourVolume::parse(&rbsp[2..20]).is_err());
+    }
+}
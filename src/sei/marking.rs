@@ -0,0 +1,79 @@
+use crate::bitreader::BitReader;
+use crate::eg::read_ue;
+use crate::slice::DecRefPicMarking;
+use crate::sps::Sps;
+use crate::Result;
+
+/// Decoded reference picture marking repetition SEI (D.1.8): the marking
+/// of an earlier reference picture, so it can be restored if that
+/// picture's slices are lost.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecRefPicMarkingRepetition {
+    pub original_idr_flag: bool,
+    pub original_frame_num: u32,
+    pub original_field_pic_flag: bool,
+    pub original_bottom_field_flag: bool,
+    pub dec_ref_pic_marking: DecRefPicMarking,
+}
+
+impl DecRefPicMarkingRepetition {
+    pub fn parse(data: &[u8], sps: &Sps) -> Result<Self> {
+        let mut reader = BitReader::new(data);
+
+        let original_idr_flag = reader.read_flag()?;
+        let original_frame_num = read_ue(&mut reader)?;
+
+        let mut original_field_pic_flag = false;
+        let mut original_bottom_field_flag = false;
+        if !sps.frame_mbs_only_flag {
+            original_field_pic_flag = reader.read_flag()?;
+            if original_field_pic_flag {
+                original_bottom_field_flag = reader.read_flag()?;
+            }
+        }
+
+        Ok(DecRefPicMarkingRepetition {
+            original_idr_flag,
+            original_frame_num,
+            original_field_pic_flag,
+            original_bottom_field_flag,
+            dec_ref_pic_marking: DecRefPicMarking::parse(&mut reader, original_idr_flag)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitreader::pack_bit_string;
+    use crate::slice::MemoryManagementControlOperation;
+    use crate::sps::test_sps;
+
+    #[test]
+    fn test_adaptive_marking() {
+        let mut sps = test_sps();
+
+        // frame_num 2, MMCO 1 with difference_of_pic_nums_minus1 0, then end
+        let data = pack_bit_string("0 011 1 010 1 1");
+        let repetition = DecRefPicMarkingRepetition::parse(&data, &sps).unwrap();
+        assert!(!repetition.original_idr_flag);
+        assert_eq!(repetition.original_frame_num, 2);
+        assert!(!repetition.original_field_pic_flag);
+        assert!(repetition.dec_ref_pic_marking.adaptive_ref_pic_marking_mode_flag);
+        assert_eq!(
+            repetition.dec_ref_pic_marking.mmcos,
+            [MemoryManagementControlOperation::MarkShortTermUnused {
+                difference_of_pic_nums_minus1: 0
+            }]
+        );
+
+        sps.frame_mbs_only_flag = false;
+        let data = pack_bit_string("1 1 1 1 0 1");
+        let repetition = DecRefPicMarkingRepetition::parse(&data, &sps).unwrap();
+        assert!(repetition.original_idr_flag);
+        assert!(repetition.original_field_pic_flag);
+        assert!(repetition.original_bottom_field_flag);
+        assert!(!repetition.dec_ref_pic_marking.no_output_of_prior_pics_flag);
+        assert!(repetition.dec_ref_pic_marking.long_term_reference_flag);
+    }
+}
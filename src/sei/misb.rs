@@ -0,0 +1,172 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// uuid_iso_iec_11578 of the MISB ST 0604 precision time stamp SEI.
+pub const UUID_MISB_PRECISION_TIME_STAMP: [u8; 16] = *b"MISPmicrosectime";
+
+/// Leading bytes of every SMPTE Universal Label.
+pub const SMPTE_UL_PREFIX: [u8; 4] = [0x06, 0x0e, 0x2b, 0x34];
+
+/// Universal Label of the MISB ST 0601 UAS Datalink Local Set.
+pub const UAS_DATALINK_LS_KEY: [u8; 16] = [
+    0x06, 0x0e, 0x2b, 0x34, 0x02, 0x0b, 0x01, 0x01,
+    0x0e, 0x01, 0x03, 0x01, 0x01, 0x00, 0x00, 0x00,
+];
+
+/// MISB ST 0604 precision time stamp: microseconds since the POSIX epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrecisionTimeStamp {
+    /// Time stamp status byte (lock, discontinuity and reverse flags).
+    pub status: u8,
+    pub microseconds: u64,
+}
+
+impl PrecisionTimeStamp {
+    /// Parses a user_data_unregistered payload, returning `None` unless it
+    /// carries the MISB UUID. The eight time stamp bytes are split into
+    /// pairs separated by 0xff to keep start codes out of the payload.
+    pub fn from_user_data_unregistered(payload: &[u8]) -> Option<Self> {
+        let data = payload.strip_prefix(&UUID_MISB_PRECISION_TIME_STAMP[..])?;
+        let (&status, data) = data.split_first()?;
+        let data = data.get(..11)?;
+
+        let mut microseconds = 0u64;
+        for (i, chunk) in data.chunks(3).enumerate() {
+            if i < 3 && chunk[2] != 0xff {
+                return None;
+            }
+            microseconds = (microseconds << 16) | u16::from_be_bytes([chunk[0], chunk[1]]) as u64;
+        }
+
+        Some(PrecisionTimeStamp { status, microseconds })
+    }
+
+    pub fn to_system_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_micros(self.microseconds)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KlvItem {
+    pub tag: u64,
+    pub value: Vec<u8>,
+}
+
+/// A KLV local set carried in user_data_unregistered, with the set's
+/// 16-byte Universal Label in place of the UUID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KlvLocalSet {
+    pub key: [u8; 16],
+    pub items: Vec<KlvItem>,
+}
+
+impl KlvLocalSet {
+    /// Parses a user_data_unregistered payload whose UUID is a SMPTE
+    /// Universal Label followed by the BER length and the local set.
+    pub fn from_user_data_unregistered(payload: &[u8]) -> Option<Self> {
+        if !payload.starts_with(&SMPTE_UL_PREFIX) || payload.len() < 16 {
+            return None;
+        }
+        let (key, mut data) = payload.split_at(16);
+
+        let length = read_ber_length(&mut data)?;
+        let mut value = data.get(..length)?;
+
+        let mut items = Vec::new();
+        while !value.is_empty() {
+            let tag = read_ber_oid(&mut value)?;
+            let length = read_ber_length(&mut value)?;
+            items.push(KlvItem { tag, value: value.get(..length)?.to_vec() });
+            value = &value[length..];
+        }
+
+        Some(KlvLocalSet { key: key.try_into().ok()?, items })
+    }
+
+    pub fn get(&self, tag: u64) -> Option<&[u8]> {
+        self.items
+            .iter()
+            .find(|item| item.tag == tag)
+            .map(|item| item.value.as_slice())
+    }
+}
+
+/// BER short or long form length.
+fn read_ber_length(data: &mut &[u8]) -> Option<usize> {
+    let (&first, rest) = data.split_first()?;
+    *data = rest;
+    if first < 0x80 {
+        return Some(first as usize);
+    }
+
+    let count = (first & 0x7f) as usize;
+    if count > 8 {
+        return None;
+    }
+    let bytes = data.get(..count)?;
+    *data = &data[count..];
+    Some(bytes.iter().fold(0usize, |len, &b| (len << 8) | b as usize))
+}
+
+/// BER-OID encoded tag: seven bits per byte, high bit set on all but the
+/// last byte.
+fn read_ber_oid(data: &mut &[u8]) -> Option<u64> {
+    let mut tag = 0u64;
+    for i in 0..9 {
+        let byte = *data.get(i)?;
+        tag = (tag << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            *data = &data[i + 1..];
+            return Some(tag);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precision_time_stamp() {
+        // 2024-01-01T00:00:00.000001Z
+        let microseconds: u64 = 1_704_067_200_000_001;
+        let ts = microseconds.to_be_bytes();
+
+        let mut payload = UUID_MISB_PRECISION_TIME_STAMP.to_vec();
+        payload.extend_from_slice(&[0x1f, ts[0], ts[1], 0xff, ts[2], ts[3], 0xff]);
+        payload.extend_from_slice(&[ts[4], ts[5], 0xff, ts[6], ts[7]]);
+
+        let stamp = PrecisionTimeStamp::from_user_data_unregistered(&payload).unwrap();
+        assert_eq!(stamp, PrecisionTimeStamp { status: 0x1f, microseconds });
+        assert_eq!(
+            stamp.to_system_time().duration_since(UNIX_EPOCH).unwrap(),
+            Duration::new(1_704_067_200, 1_000)
+        );
+
+        payload[19] = 0x00;
+        assert!(PrecisionTimeStamp::from_user_data_unregistered(&payload).is_none());
+        assert!(PrecisionTimeStamp::from_user_data_unregistered(&payload[..25]).is_none());
+    }
+
+    #[test]
+    fn test_klv_local_set() {
+        let mut payload = UAS_DATALINK_LS_KEY.to_vec();
+        payload.push(0x81);
+        payload.push(0x0e);
+        payload.extend_from_slice(&[0x02, 0x08, 0x00, 0x06, 0x0e, 0x1a, 0x3b, 0x53]);
+        payload.extend_from_slice(&[0x00, 0x01, 0x81, 0x01, 0x01, 0x0d]);
+
+        let set = KlvLocalSet::from_user_data_unregistered(&payload).unwrap();
+        assert_eq!(set.key, UAS_DATALINK_LS_KEY);
+        assert_eq!(set.items.len(), 2);
+        assert_eq!(set.get(2), Some(&[0x00, 0x06, 0x0e, 0x1a, 0x3b, 0x53, 0x00, 0x01][..]));
+        assert_eq!(set.get(129), Some(&[0x0d][..]));
+        assert_eq!(set.get(65), None);
+
+        // Local set longer than the payload
+        payload[17] = 0x0f;
+        assert!(KlvLocalSet::from_user_data_unregistered(&payload).is_none());
+        let timestamp_uuid = UUID_MISB_PRECISION_TIME_STAMP;
+        assert!(KlvLocalSet::from_user_data_unregistered(&timestamp_uuid).is_none());
+    }
+}
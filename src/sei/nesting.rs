@@ -0,0 +1,202 @@
+use super::SeiMessage;
+use crate::bitreader::BitReader;
+use crate::eg::read_ue;
+use crate::sps::Sps;
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayerRepresentation {
+    pub sei_dependency_id: u8,
+    pub sei_quality_id: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NestedLayers {
+    pub representations: Vec<LayerRepresentation>,
+    pub sei_temporal_id: u8,
+}
+
+/// Scalable nesting SEI (G.13.1.1). `layers` is `None` when the nested
+/// messages apply to all layer representations of the access unit.
+#[derive(Debug, Clone)]
+pub struct ScalableNesting {
+    pub layers: Option<NestedLayers>,
+    pub messages: Vec<SeiMessage>,
+}
+
+impl ScalableNesting {
+    pub fn parse(data: &[u8], sps: Option<&Sps>) -> Result<Self> {
+        let mut reader = BitReader::new(data);
+
+        let layers = if reader.read_flag()? {
+            None
+        } else {
+            let count = read_count(&mut reader, "num_layer_representations_minus1")?;
+            let representations = (0..count)
+                .map(|_| {
+                    Ok(LayerRepresentation {
+                        sei_dependency_id: reader.read_bits(3)? as u8,
+                        sei_quality_id: reader.read_bits(4)? as u8,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Some(NestedLayers {
+                representations,
+                sei_temporal_id: reader.read_bits(3)? as u8,
+            })
+        };
+
+        Ok(ScalableNesting {
+            layers,
+            messages: parse_nested_messages(reader, data, sps)?,
+        })
+    }
+}
+
+/// The view components an MVC nesting SEI applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MvcNestingTarget {
+    AllViewComponents,
+    ViewComponents(Vec<u16>),
+    OperationPoint {
+        sei_op_view_ids: Vec<u16>,
+        sei_op_temporal_id: u8,
+    },
+}
+
+/// MVC scalable nesting SEI (H.13.1.1).
+#[derive(Debug, Clone)]
+pub struct MvcScalableNesting {
+    pub target: MvcNestingTarget,
+    pub messages: Vec<SeiMessage>,
+}
+
+impl MvcScalableNesting {
+    pub fn parse(data: &[u8], sps: Option<&Sps>) -> Result<Self> {
+        let mut reader = BitReader::new(data);
+
+        let target = if !reader.read_flag()? {
+            if reader.read_flag()? {
+                MvcNestingTarget::AllViewComponents
+            } else {
+                MvcNestingTarget::ViewComponents(read_view_ids(
+                    &mut reader,
+                    "num_view_components_minus1",
+                )?)
+            }
+        } else {
+            MvcNestingTarget::OperationPoint {
+                sei_op_view_ids: read_view_ids(&mut reader, "num_view_components_op_minus1")?,
+                sei_op_temporal_id: reader.read_bits(3)? as u8,
+            }
+        };
+
+        Ok(MvcScalableNesting {
+            target,
+            messages: parse_nested_messages(reader, data, sps)?,
+        })
+    }
+}
+
+fn read_count(reader: &mut BitReader, name: &str) -> Result<u32> {
+    let minus1 = read_ue(reader)?;
+    // Both layer and view counts are limited to 1024 entries
+    if minus1 > 1023 {
+        return Err(Error::BitstreamError(format!("Invalid {} {}", name, minus1)));
+    }
+    Ok(minus1 + 1)
+}
+
+fn read_view_ids(reader: &mut BitReader, name: &str) -> Result<Vec<u16>> {
+    (0..read_count(reader, name)?)
+        .map(|_| Ok(reader.read_bits(10)? as u16))
+        .collect()
+}
+
+fn parse_nested_messages(
+    mut reader: BitReader,
+    data: &[u8],
+    sps: Option<&Sps>,
+) -> Result<Vec<SeiMessage>> {
+    // sei_nesting_zero_bit up to the byte boundary
+    reader.align_to_byte();
+    let (byte_pos, _) = reader.position();
+    SeiMessage::parse_nested(data.get(byte_pos..).unwrap_or_default(), sps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitreader::pack_bit_string;
+    use crate::sei::SeiPayload;
+
+    #[test]
+    fn test_scalable_nesting() {
+        let mut data = pack_bit_string(concat!(
+            "0 010", // two layer representations
+            "001 0000 010 0001", // (1, 0), (2, 1)
+            "011 000", // sei_temporal_id 3, zero bits
+        ));
+        // Nested recovery point
+        data.extend_from_slice(&[0x06, 0x01, 0x84]);
+
+        let nesting = ScalableNesting::parse(&data, None).unwrap();
+        let layers = nesting.layers.unwrap();
+        assert_eq!(
+            layers.representations,
+            [
+                LayerRepresentation { sei_dependency_id: 1, sei_quality_id: 0 },
+                LayerRepresentation { sei_dependency_id: 2, sei_quality_id: 1 },
+            ]
+        );
+        assert_eq!(layers.sei_temporal_id, 3);
+        assert_eq!(nesting.messages.len(), 1);
+        assert!(matches!(nesting.messages[0].payload, SeiPayload::RecoveryPoint { .. }));
+
+        let all_layers = ScalableNesting::parse(&[0x80, 0x06, 0x01, 0x84], None).unwrap();
+        assert!(all_layers.layers.is_none());
+        assert_eq!(all_layers.messages.len(), 1);
+    }
+
+    #[test]
+    fn test_mvc_scalable_nesting() {
+        let mut data = pack_bit_string("0 0 010 0000000001 0000000010 0000000");
+        data.extend_from_slice(&[0x06, 0x01, 0x84]);
+        let nesting = MvcScalableNesting::parse(&data, None).unwrap();
+        assert_eq!(nesting.target, MvcNestingTarget::ViewComponents(vec![1, 2]));
+        assert_eq!(nesting.messages.len(), 1);
+
+        let mut data = pack_bit_string("1 1 0000000011 101 0");
+        data.extend_from_slice(&[0x06, 0x01, 0x84]);
+        let nesting = MvcScalableNesting::parse(&data, None).unwrap();
+        assert_eq!(
+            nesting.target,
+            MvcNestingTarget::OperationPoint { sei_op_view_ids: vec![3], sei_op_temporal_id: 5 }
+        );
+
+        // Nesting SEI must not contain further nesting SEI
+        let nested = MvcScalableNesting::parse(&[0x40, 0x25, 0x02, 0x40, 0x06], None).unwrap();
+        assert!(matches!(nested.messages[0].payload, SeiPayload::Unknown(37, _)));
+    }
+
+    #[test]
+    fn test_bad_nested_payload_keeps_nesting() {
+        let rbsp = [
+            0x1e, 0x09, 0x80,
+            // Tone mapping with a target bit depth of 0
+            0x17, 0x03, 0xa1, 0x00, 0x00,
+            // Recovery point
+            0x06, 0x01, 0x84,
+            0x80,
+        ];
+
+        let messages = SeiMessage::parse(&rbsp).unwrap();
+        assert_eq!(messages.len(), 1);
+        let SeiPayload::ScalableNesting(nesting) = &messages[0].payload else {
+            panic!("Expected ScalableNesting payload, got {:?}", messages[0].payload);
+        };
+        assert_eq!(nesting.messages.len(), 2);
+        assert!(matches!(nesting.messages[0].payload, SeiPayload::Unknown(23, _)));
+        assert!(matches!(nesting.messages[1].payload, SeiPayload::RecoveryPoint { .. }));
+    }
+}
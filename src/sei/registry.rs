@@ -0,0 +1,241 @@
+use super::{SeiMessage, SeiPayload, UserDataRegistered};
+use crate::au::AccessUnit;
+use crate::Result;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+const USER_DATA_REGISTERED: u32 = 4;
+const USER_DATA_UNREGISTERED: u32 = 5;
+
+/// Selects which SEI payloads a handler receives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SeiHandlerKey {
+    /// Any payload of this type. The handler gets the whole payload.
+    PayloadType(u32),
+    /// user_data_unregistered with this uuid_iso_iec_11578. The handler gets
+    /// the bytes following the UUID.
+    Uuid([u8; 16]),
+    /// user_data_registered_itu_t_t35 from this country and provider. The
+    /// handler gets the bytes following the country and provider codes;
+    /// the provider code is `None` for countries that do not define one.
+    T35 { country_code: u8, country_code_extension: Option<u8>, provider_code: Option<u16> },
+}
+
+type Handler = Box<dyn Fn(&[u8]) -> Result<Arc<dyn Any + Send + Sync>> + Send + Sync>;
+
+/// A payload decoded by a registered handler.
+#[derive(Clone)]
+pub struct CustomSei {
+    pub key: SeiHandlerKey,
+    pub payload_type: u32,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+impl CustomSei {
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    pub fn value(&self) -> &(dyn Any + Send + Sync) {
+        &*self.value
+    }
+}
+
+impl fmt::Debug for CustomSei {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomSei")
+            .field("key", &self.key)
+            .field("payload_type", &self.payload_type)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Caller-supplied decoders for SEI payloads the crate does not know about.
+/// UUID and T.35 handlers take precedence over a handler for the payload
+/// type that carries them. Payloads a handler fails to decode are skipped.
+#[derive(Default)]
+pub struct SeiHandlerRegistry {
+    handlers: HashMap<SeiHandlerKey, Handler>,
+}
+
+impl SeiHandlerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `decode` for `key`, replacing any earlier handler.
+    pub fn register<T, F>(&mut self, key: SeiHandlerKey, decode: F)
+    where
+        T: Any + Send + Sync,
+        F: Fn(&[u8]) -> Result<T> + Send + Sync + 'static,
+    {
+        self.handlers.insert(
+            key,
+            Box::new(move |data| Ok(Arc::new(decode(data)?) as Arc<dyn Any + Send + Sync>)),
+        );
+    }
+
+    pub fn unregister(&mut self, key: SeiHandlerKey) -> bool {
+        self.handlers.remove(&key).is_some()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    pub fn decode(&self, payload_type: u32, payload: &[u8]) -> Option<CustomSei> {
+        let (key, data) = self
+            .specific_key(payload_type, payload)
+            .filter(|(key, _)| self.handlers.contains_key(key))
+            .unwrap_or((SeiHandlerKey::PayloadType(payload_type), payload));
+
+        let value = self.handlers.get(&key)?(data).ok()?;
+        Some(CustomSei { key, payload_type, value })
+    }
+
+    /// Decodes the parsed SEI messages of `au`, including those carried in
+    /// nesting SEI.
+    pub fn decode_access_unit(&self, au: &AccessUnit) -> Vec<CustomSei> {
+        let mut decoded = Vec::new();
+        if !self.is_empty() {
+            self.decode_messages(&au.sei_messages, &mut decoded);
+        }
+        decoded
+    }
+
+    fn decode_messages(&self, messages: &[SeiMessage], decoded: &mut Vec<CustomSei>) {
+        for message in messages {
+            if let Ok(payload) = message.payload_bytes() {
+                decoded.extend(self.decode(message.payload_type, &payload));
+            }
+
+            match &message.payload {
+                SeiPayload::ScalableNesting(nesting) => {
+                    self.decode_messages(&nesting.messages, decoded);
+                }
+                SeiPayload::MvcScalableNesting(nesting) => {
+                    self.decode_messages(&nesting.messages, decoded);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn specific_key<'a>(
+        &self,
+        payload_type: u32,
+        payload: &'a [u8],
+    ) -> Option<(SeiHandlerKey, &'a [u8])> {
+        match payload_type {
+            USER_DATA_UNREGISTERED if payload.len() >= 16 => {
+                let (uuid, data) = payload.split_at(16);
+                Some((SeiHandlerKey::Uuid(uuid.try_into().ok()?), data))
+            }
+            USER_DATA_REGISTERED => {
+                let registered = UserDataRegistered::parse(payload).ok()?;
+                let key = SeiHandlerKey::T35 {
+                    country_code: registered.country_code,
+                    country_code_extension: registered.country_code_extension,
+                    provider_code: registered.provider_code,
+                };
+                Some((key, &payload[payload.len() - registered.data.len()..]))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for SeiHandlerRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.handlers.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    const UUID: [u8; 16] = [
+        0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17,
+        0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f,
+    ];
+
+    #[derive(Debug, PartialEq)]
+    struct Marker(Vec<u8>);
+
+    #[test]
+    fn test_dispatch_by_key() {
+        let mut registry = SeiHandlerRegistry::new();
+        registry.register(SeiHandlerKey::Uuid(UUID), |data| Ok(Marker(data.to_vec())));
+        registry.register(
+            SeiHandlerKey::T35 {
+                country_code: 0xb5,
+                country_code_extension: None,
+                provider_code: Some(0x003c),
+            },
+            |data| Ok(data.len()),
+        );
+        registry.register(
+            SeiHandlerKey::T35 {
+                country_code: 0xff,
+                country_code_extension: Some(0x01),
+                provider_code: None,
+            },
+            |data| Ok(data.to_vec()),
+        );
+        registry.register(SeiHandlerKey::PayloadType(5), |_| Ok("unregistered"));
+        registry.register(SeiHandlerKey::PayloadType(200), |data| {
+            data.first().copied().ok_or(Error::UnexpectedEof)
+        });
+
+        let mut payload = UUID.to_vec();
+        payload.extend_from_slice(&[0xaa, 0xbb]);
+        let custom = registry.decode(5, &payload).unwrap();
+        assert_eq!(custom.key, SeiHandlerKey::Uuid(UUID));
+        assert_eq!(custom.downcast_ref::<Marker>(), Some(&Marker(vec![0xaa, 0xbb])));
+        assert_eq!(custom.downcast_ref::<usize>(), None);
+
+        // Unknown UUIDs fall back to the payload type handler
+        payload[0] = 0;
+        let custom = registry.decode(5, &payload).unwrap();
+        assert_eq!(custom.key, SeiHandlerKey::PayloadType(5));
+        assert_eq!(custom.downcast_ref::<&str>(), Some(&"unregistered"));
+
+        let custom = registry.decode(4, &[0xb5, 0x00, 0x3c, 0x01, 0x02, 0x03]).unwrap();
+        assert_eq!(custom.downcast_ref::<usize>(), Some(&3));
+        assert!(registry.decode(4, &[0xb5, 0x00, 0x31, 0x01]).is_none());
+        let custom = registry.decode(4, &[0xff, 0x01, 0x12, 0x34]).unwrap();
+        assert_eq!(custom.downcast_ref::<Vec<u8>>(), Some(&vec![0x12, 0x34]));
+        assert!(registry.decode(4, &[0xff, 0x02, 0x12, 0x34]).is_none());
+
+        assert_eq!(registry.decode(200, &[7]).unwrap().downcast_ref::<u8>(), Some(&7));
+        assert!(registry.decode(200, &[]).is_none());
+
+        assert!(registry.unregister(SeiHandlerKey::PayloadType(200)));
+        assert!(registry.decode(200, &[7]).is_none());
+    }
+
+    #[test]
+    fn test_decodes_nested_messages() {
+        use crate::nal::Nal;
+
+        let mut registry = SeiHandlerRegistry::new();
+        registry.register(SeiHandlerKey::Uuid(UUID), |data| Ok(Marker(data.to_vec())));
+
+        // Scalable nesting for all layers around one user_data_unregistered
+        let mut ebsp = vec![0x06, 0x1e, 0x15, 0x80, 0x05, 0x12];
+        ebsp.extend_from_slice(&UUID);
+        ebsp.extend_from_slice(&[0xaa, 0xbb, 0x80]);
+        let mut au = AccessUnit::new();
+        au.add_nal(Nal::parse(4, &ebsp).unwrap());
+        au.parse_sei_messages();
+
+        let custom = registry.decode_access_unit(&au);
+        assert_eq!(custom.len(), 1);
+        assert_eq!(custom[0].key, SeiHandlerKey::Uuid(UUID));
+        assert_eq!(custom[0].downcast_ref::<Marker>(), Some(&Marker(vec![0xaa, 0xbb])));
+    }
+}
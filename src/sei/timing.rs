@@ -0,0 +1,367 @@
+use crate::bitreader::BitReader;
+use crate::eg::read_ue;
+use crate::sps::Sps;
+use crate::vui::HrdParameters;
+use crate::{Error, Result};
+
+/// Initial CPB removal delay and offset for one SchedSelIdx, in units of a
+/// 90 kHz clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InitialCpbRemovalDelay {
+    pub initial_cpb_removal_delay: u32,
+    pub initial_cpb_removal_delay_offset: u32,
+}
+
+impl InitialCpbRemovalDelay {
+    pub fn delay_seconds(&self) -> f64 {
+        self.initial_cpb_removal_delay as f64 / 90_000.0
+    }
+
+    pub fn offset_seconds(&self) -> f64 {
+        self.initial_cpb_removal_delay_offset as f64 / 90_000.0
+    }
+}
+
+/// Buffering period SEI (D.1.2). The delays are indexed by SchedSelIdx and
+/// present only for the HRDs signalled in the SPS VUI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferingPeriod {
+    pub seq_parameter_set_id: u8,
+    pub nal_initial_cpb_removal_delays: Vec<InitialCpbRemovalDelay>,
+    pub vcl_initial_cpb_removal_delays: Vec<InitialCpbRemovalDelay>,
+}
+
+impl BufferingPeriod {
+    /// Fails when the message names a different SPS than `sps`, since the
+    /// delays can only be read with the HRD parameters of the named SPS.
+    /// [`SeiMessage`](super::SeiMessage) then reports it as `Unknown`.
+    pub fn parse(data: &[u8], sps: &Sps) -> Result<Self> {
+        let mut reader = BitReader::new(data);
+
+        let seq_parameter_set_id = read_ue(&mut reader)?;
+        if seq_parameter_set_id != sps.seq_parameter_set_id as u32 {
+            return Err(Error::BitstreamError(format!(
+                "Buffering period refers to SPS {} but SPS {} is active",
+                seq_parameter_set_id, sps.seq_parameter_set_id
+            )));
+        }
+
+        let vui = sps.vui.as_ref();
+        let nal_initial_cpb_removal_delays =
+            read_initial_cpb_removal_delays(&mut reader, vui.and_then(|v| v.nal_hrd_parameters.as_ref()))?;
+        let vcl_initial_cpb_removal_delays =
+            read_initial_cpb_removal_delays(&mut reader, vui.and_then(|v| v.vcl_hrd_parameters.as_ref()))?;
+
+        Ok(BufferingPeriod {
+            seq_parameter_set_id: seq_parameter_set_id as u8,
+            nal_initial_cpb_removal_delays,
+            vcl_initial_cpb_removal_delays,
+        })
+    }
+}
+
+/// pic_struct values from Table D-1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PicStruct {
+    Frame,
+    TopField,
+    BottomField,
+    TopBottom,
+    BottomTop,
+    TopBottomTop,
+    BottomTopBottom,
+    FrameDoubling,
+    FrameTripling,
+    Reserved(u8),
+}
+
+impl PicStruct {
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            Self::Frame => 0,
+            Self::TopField => 1,
+            Self::BottomField => 2,
+            Self::TopBottom => 3,
+            Self::BottomTop => 4,
+            Self::TopBottomTop => 5,
+            Self::BottomTopBottom => 6,
+            Self::FrameDoubling => 7,
+            Self::FrameTripling => 8,
+            Self::Reserved(v) => *v,
+        }
+    }
+
+    /// NumClockTS, the number of clock timestamp sets that may follow.
+    pub fn num_clock_ts(&self) -> usize {
+        match self {
+            Self::Frame | Self::TopField | Self::BottomField => 1,
+            Self::TopBottom | Self::BottomTop | Self::FrameDoubling => 2,
+            Self::TopBottomTop | Self::BottomTopBottom | Self::FrameTripling => 3,
+            Self::Reserved(_) => 0,
+        }
+    }
+}
+
+impl From<u8> for PicStruct {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Frame,
+            1 => Self::TopField,
+            2 => Self::BottomField,
+            3 => Self::TopBottom,
+            4 => Self::BottomTop,
+            5 => Self::TopBottomTop,
+            6 => Self::BottomTopBottom,
+            7 => Self::FrameDoubling,
+            8 => Self::FrameTripling,
+            _ => Self::Reserved(value),
+        }
+    }
+}
+
+/// One clock timestamp set of a picture timing SEI. `seconds`, `minutes`
+/// and `hours` are `None` when the set leaves them out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockTimestamp {
+    pub ct_type: u8,
+    pub nuit_field_based_flag: bool,
+    pub counting_type: u8,
+    pub full_timestamp_flag: bool,
+    pub discontinuity_flag: bool,
+    pub cnt_dropped_flag: bool,
+    pub n_frames: u8,
+    pub seconds: Option<u8>,
+    pub minutes: Option<u8>,
+    pub hours: Option<u8>,
+    pub time_offset: i32,
+}
+
+impl ClockTimestamp {
+    fn parse(reader: &mut BitReader, time_offset_length: u8) -> Result<Self> {
+        let ct_type = reader.read_bits(2)? as u8;
+        let nuit_field_based_flag = reader.read_flag()?;
+        let counting_type = reader.read_bits(5)? as u8;
+        let full_timestamp_flag = reader.read_flag()?;
+        let discontinuity_flag = reader.read_flag()?;
+        let cnt_dropped_flag = reader.read_flag()?;
+        let n_frames = reader.read_u8()?;
+
+        let (mut seconds, mut minutes, mut hours) = (None, None, None);
+        if full_timestamp_flag {
+            seconds = Some(reader.read_bits(6)? as u8);
+            minutes = Some(reader.read_bits(6)? as u8);
+            hours = Some(reader.read_bits(5)? as u8);
+        } else if reader.read_flag()? {
+            seconds = Some(reader.read_bits(6)? as u8);
+            if reader.read_flag()? {
+                minutes = Some(reader.read_bits(6)? as u8);
+                if reader.read_flag()? {
+                    hours = Some(reader.read_bits(5)? as u8);
+                }
+            }
+        }
+
+        let time_offset = if time_offset_length > 0 {
+            let len = time_offset_length as u32;
+            let raw = reader.read_bits(len)?;
+            // i(v): two's complement over time_offset_length bits
+            ((raw << (32 - len)) as i32) >> (32 - len)
+        } else {
+            0
+        };
+
+        Ok(ClockTimestamp {
+            ct_type,
+            nuit_field_based_flag,
+            counting_type,
+            full_timestamp_flag,
+            discontinuity_flag,
+            cnt_dropped_flag,
+            n_frames,
+            seconds,
+            minutes,
+            hours,
+            time_offset,
+        })
+    }
+}
+
+/// Picture timing SEI (D.1.3). The CPB/DPB delays are present when the SPS
+/// VUI signals NAL or VCL HRD parameters, `pic_struct` and the clock
+/// timestamps when it sets pic_struct_present_flag. `clock_timestamps` has
+/// NumClockTS entries, `None` where clock_timestamp_flag is 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PicTiming {
+    pub cpb_removal_delay: Option<u32>,
+    pub dpb_output_delay: Option<u32>,
+    pub pic_struct: Option<PicStruct>,
+    pub clock_timestamps: Vec<Option<ClockTimestamp>>,
+}
+
+impl PicTiming {
+    pub fn parse(data: &[u8], sps: &Sps) -> Result<Self> {
+        let mut reader = BitReader::new(data);
+        let vui = sps.vui.as_ref();
+
+        // Delay lengths come from the NAL HRD when both are present; E.2.2
+        // requires them to match
+        let hrd = vui.and_then(|v| v.nal_hrd_parameters.as_ref().or(v.vcl_hrd_parameters.as_ref()));
+
+        let (cpb_removal_delay, dpb_output_delay) = match hrd {
+            Some(hrd) => (
+                Some(reader.read_bits(hrd.cpb_removal_delay_length_minus1 as u32 + 1)?),
+                Some(reader.read_bits(hrd.dpb_output_delay_length_minus1 as u32 + 1)?),
+            ),
+            None => (None, None),
+        };
+
+        let mut pic_struct = None;
+        let mut clock_timestamps = Vec::new();
+
+        if vui.is_some_and(|v| v.pic_struct_present_flag) {
+            let value = PicStruct::from(reader.read_bits(4)? as u8);
+            let time_offset_length = hrd.map_or(24, |hrd| hrd.time_offset_length);
+
+            for _ in 0..value.num_clock_ts() {
+                clock_timestamps.push(if reader.read_flag()? {
+                    Some(ClockTimestamp::parse(&mut reader, time_offset_length)?)
+                } else {
+                    None
+                });
+            }
+            pic_struct = Some(value);
+        }
+
+        Ok(PicTiming {
+            cpb_removal_delay,
+            dpb_output_delay,
+            pic_struct,
+            clock_timestamps,
+        })
+    }
+}
+
+fn read_initial_cpb_removal_delays(
+    reader: &mut BitReader,
+    hrd: Option<&HrdParameters>,
+) -> Result<Vec<InitialCpbRemovalDelay>> {
+    let Some(hrd) = hrd else {
+        return Ok(Vec::new());
+    };
+
+    let len = hrd.initial_cpb_removal_delay_length_minus1 as u32 + 1;
+    (0..=hrd.cpb_cnt_minus1)
+        .map(|_| {
+            Ok(InitialCpbRemovalDelay {
+                initial_cpb_removal_delay: reader.read_bits(len)?,
+                initial_cpb_removal_delay_offset: reader.read_bits(len)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitreader::pack_bit_string;
+    use crate::sps::test_sps;
+    use crate::vui::test_vui;
+
+    fn hrd(cpb_cnt_minus1: u8, initial_cpb_removal_delay_length_minus1: u8) -> HrdParameters {
+        HrdParameters {
+            cpb_cnt_minus1,
+            bit_rate_scale: 0,
+            cpb_size_scale: 0,
+            bit_rate_value_minus1: vec![0; cpb_cnt_minus1 as usize + 1],
+            cpb_size_value_minus1: vec![0; cpb_cnt_minus1 as usize + 1],
+            cbr_flag: vec![false; cpb_cnt_minus1 as usize + 1],
+            initial_cpb_removal_delay_length_minus1,
+            cpb_removal_delay_length_minus1: 23,
+            dpb_output_delay_length_minus1: 23,
+            time_offset_length: 24,
+        }
+    }
+
+    #[test]
+    fn test_buffering_period_nal_and_vcl() {
+        let mut sps = test_sps();
+        let mut vui = test_vui();
+        vui.nal_hrd_parameters = Some(hrd(1, 7));
+        vui.vcl_hrd_parameters = Some(hrd(0, 15));
+        sps.vui = Some(vui);
+
+        // ue(0), NAL: (0x12, 0x34), (0x56, 0x78), VCL: (0x9abc, 0xdef0)
+        let data = [0x89, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e, 0x6f, 0x78, 0x00];
+        let bp = BufferingPeriod::parse(&data, &sps).unwrap();
+
+        assert_eq!(bp.seq_parameter_set_id, 0);
+        assert_eq!(
+            bp.nal_initial_cpb_removal_delays,
+            vec![
+                InitialCpbRemovalDelay { initial_cpb_removal_delay: 0x12, initial_cpb_removal_delay_offset: 0x34 },
+                InitialCpbRemovalDelay { initial_cpb_removal_delay: 0x56, initial_cpb_removal_delay_offset: 0x78 },
+            ]
+        );
+        assert_eq!(
+            bp.vcl_initial_cpb_removal_delays,
+            vec![InitialCpbRemovalDelay { initial_cpb_removal_delay: 0x9abc, initial_cpb_removal_delay_offset: 0xdef0 }]
+        );
+
+        // Refers to SPS 1
+        assert!(BufferingPeriod::parse(&[0x40], &sps).is_err());
+    }
+
+    #[test]
+    fn test_pic_timing_with_clock_timestamps() {
+        let mut sps = test_sps();
+        let mut vui = test_vui();
+        let mut nal_hrd = hrd(0, 23);
+        nal_hrd.cpb_removal_delay_length_minus1 = 7;
+        nal_hrd.dpb_output_delay_length_minus1 = 3;
+        nal_hrd.time_offset_length = 5;
+        vui.nal_hrd_parameters = Some(nal_hrd);
+        vui.pic_struct_present_flag = true;
+        sps.vui = Some(vui);
+
+        let data = pack_bit_string(concat!(
+            "00000110 0010", // cpb_removal_delay 6, dpb_output_delay 2
+            "0011", // pic_struct TopBottom
+            // Full timestamp 01:02:03 frame 4, drop-frame counting, offset -3
+            "1 00 0 00100 1 0 1 00000100 000011 000010 00001 11101",
+            // Partial timestamp carrying seconds and minutes
+            "1 00 0 00100 0 0 0 00000101 1 000100 1 000010 0 00000",
+        ));
+
+        let timing = PicTiming::parse(&data, &sps).unwrap();
+        assert_eq!(timing.cpb_removal_delay, Some(6));
+        assert_eq!(timing.dpb_output_delay, Some(2));
+        assert_eq!(timing.pic_struct, Some(PicStruct::TopBottom));
+        assert_eq!(timing.clock_timestamps.len(), 2);
+
+        let full = timing.clock_timestamps[0].unwrap();
+        assert_eq!(full.counting_type, 4);
+        assert!(full.cnt_dropped_flag);
+        assert_eq!(full.n_frames, 4);
+        assert_eq!((full.hours, full.minutes, full.seconds), (Some(1), Some(2), Some(3)));
+        assert_eq!(full.time_offset, -3);
+
+        let partial = timing.clock_timestamps[1].unwrap();
+        assert_eq!((partial.hours, partial.minutes, partial.seconds), (None, Some(2), Some(4)));
+        assert_eq!(partial.n_frames, 5);
+        assert_eq!(partial.time_offset, 0);
+    }
+
+    #[test]
+    fn test_pic_timing_without_hrd() {
+        let mut sps = test_sps();
+        let mut vui = test_vui();
+        vui.pic_struct_present_flag = true;
+        sps.vui = Some(vui);
+
+        // pic_struct 7 with no clock timestamps
+        let timing = PicTiming::parse(&[0x70], &sps).unwrap();
+        assert_eq!(timing.cpb_removal_delay, None);
+        assert_eq!(timing.pic_struct, Some(PicStruct::FrameDoubling));
+        assert_eq!(timing.clock_timestamps, vec![None, None]);
+    }
+}
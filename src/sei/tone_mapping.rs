@@ -0,0 +1,235 @@
+use crate::bitreader::BitReader;
+use crate::eg::read_ue;
+use crate::{Error, Result};
+
+/// Luminance dynamic range information (tone_map_model_id 4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LuminanceDynamicRange {
+    pub camera_iso_speed_idc: u8,
+    /// Present when camera_iso_speed_idc is Extended_ISO (255).
+    pub camera_iso_speed_value: Option<u32>,
+    pub exposure_index_idc: u8,
+    pub exposure_index_value: Option<u32>,
+    pub exposure_compensation_value_sign_flag: bool,
+    pub exposure_compensation_value_numerator: u16,
+    pub exposure_compensation_value_denom_idc: u16,
+    pub ref_screen_luminance_white: u32,
+    pub extended_range_white_level: u32,
+    pub nominal_black_level_code_value: u16,
+    pub nominal_white_level_code_value: u16,
+    pub extended_white_level_code_value: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToneMapModel {
+    Linear {
+        min_value: u32,
+        max_value: u32,
+    },
+    Sigmoid {
+        sigmoid_midpoint: u32,
+        sigmoid_width: u32,
+    },
+    /// One entry per target sample value.
+    UserDefinedTable {
+        start_of_coded_interval: Vec<u32>,
+    },
+    PiecewiseLinear {
+        coded_pivot_value: Vec<u32>,
+        target_pivot_value: Vec<u32>,
+    },
+    LuminanceDynamicRange(LuminanceDynamicRange),
+    /// Model ids reserved by the spec; their syntax is unknown.
+    Reserved(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToneMapping {
+    pub tone_map_repetition_period: u32,
+    pub coded_data_bit_depth: u8,
+    pub target_bit_depth: u8,
+    pub model: ToneMapModel,
+}
+
+/// Tone mapping information SEI (D.1.24). `mapping` is `None` when the
+/// message cancels an earlier one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToneMappingInfo {
+    pub tone_map_id: u32,
+    pub mapping: Option<ToneMapping>,
+}
+
+impl ToneMappingInfo {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = BitReader::new(data);
+
+        let tone_map_id = read_ue(&mut reader)?;
+        if reader.read_flag()? {
+            return Ok(ToneMappingInfo { tone_map_id, mapping: None });
+        }
+
+        let tone_map_repetition_period = read_ue(&mut reader)?;
+        let coded_data_bit_depth = reader.read_u8()?;
+        let target_bit_depth = reader.read_u8()?;
+        if !(1..=16).contains(&target_bit_depth) || !(1..=32).contains(&coded_data_bit_depth) {
+            return Err(Error::BitstreamError(format!(
+                "Unsupported tone mapping bit depths {} -> {}",
+                coded_data_bit_depth, target_bit_depth
+            )));
+        }
+
+        // Sample values are coded in whole bytes
+        let coded_value_bits = (coded_data_bit_depth as u32).div_ceil(8) * 8;
+        let target_value_bits = (target_bit_depth as u32).div_ceil(8) * 8;
+
+        let model = match read_ue(&mut reader)? {
+            0 => ToneMapModel::Linear {
+                min_value: reader.read_bits(32)?,
+                max_value: reader.read_bits(32)?,
+            },
+            1 => ToneMapModel::Sigmoid {
+                sigmoid_midpoint: reader.read_bits(32)?,
+                sigmoid_width: reader.read_bits(32)?,
+            },
+            2 => ToneMapModel::UserDefinedTable {
+                start_of_coded_interval: (0..1u32 << target_bit_depth)
+                    .map(|_| reader.read_bits(coded_value_bits))
+                    .collect::<Result<Vec<_>>>()?,
+            },
+            3 => {
+                let num_pivots = reader.read_u16()? as usize;
+                let mut coded_pivot_value = Vec::with_capacity(num_pivots);
+                let mut target_pivot_value = Vec::with_capacity(num_pivots);
+                for _ in 0..num_pivots {
+                    coded_pivot_value.push(reader.read_bits(coded_value_bits)?);
+                    target_pivot_value.push(reader.read_bits(target_value_bits)?);
+                }
+                ToneMapModel::PiecewiseLinear {
+                    coded_pivot_value,
+                    target_pivot_value,
+                }
+            }
+            4 => ToneMapModel::LuminanceDynamicRange(parse_luminance_dynamic_range(&mut reader)?),
+            id => ToneMapModel::Reserved(id),
+        };
+
+        Ok(ToneMappingInfo {
+            tone_map_id,
+            mapping: Some(ToneMapping {
+                tone_map_repetition_period,
+                coded_data_bit_depth,
+                target_bit_depth,
+                model,
+            }),
+        })
+    }
+}
+
+fn parse_luminance_dynamic_range(reader: &mut BitReader) -> Result<LuminanceDynamicRange> {
+    const EXTENDED_ISO: u8 = 255;
+
+    let camera_iso_speed_idc = reader.read_u8()?;
+    let camera_iso_speed_value = if camera_iso_speed_idc == EXTENDED_ISO {
+        Some(reader.read_bits(32)?)
+    } else {
+        None
+    };
+
+    let exposure_index_idc = reader.read_u8()?;
+    let exposure_index_value = if exposure_index_idc == EXTENDED_ISO {
+        Some(reader.read_bits(32)?)
+    } else {
+        None
+    };
+
+    Ok(LuminanceDynamicRange {
+        camera_iso_speed_idc,
+        camera_iso_speed_value,
+        exposure_index_idc,
+        exposure_index_value,
+        exposure_compensation_value_sign_flag: reader.read_flag()?,
+        exposure_compensation_value_numerator: reader.read_u16()?,
+        exposure_compensation_value_denom_idc: reader.read_u16()?,
+        ref_screen_luminance_white: reader.read_bits(32)?,
+        extended_range_white_level: reader.read_bits(32)?,
+        nominal_black_level_code_value: reader.read_u16()?,
+        nominal_white_level_code_value: reader.read_u16()?,
+        extended_white_level_code_value: reader.read_u16()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitreader::pack_bit_string;
+
+    fn header(model_id: &str, coded_bit_depth: u8, target_bit_depth: u8) -> String {
+        // tone_map_id 0, not cancelled, repetition period 0
+        format!("1 0 1 {:08b} {:08b} {}", coded_bit_depth, target_bit_depth, model_id)
+    }
+
+    #[test]
+    fn test_linear_and_sigmoid() {
+        let bits = header("1", 10, 8) + &format!("{:032b} {:032b}", 64, 940);
+        let info = ToneMappingInfo::parse(&pack_bit_string(&bits)).unwrap();
+        let mapping = info.mapping.unwrap();
+        assert_eq!((mapping.coded_data_bit_depth, mapping.target_bit_depth), (10, 8));
+        assert_eq!(mapping.model, ToneMapModel::Linear { min_value: 64, max_value: 940 });
+
+        let bits = header("010", 10, 8) + &format!("{:032b} {:032b}", 512, 100);
+        let info = ToneMappingInfo::parse(&pack_bit_string(&bits)).unwrap();
+        assert_eq!(
+            info.mapping.unwrap().model,
+            ToneMapModel::Sigmoid { sigmoid_midpoint: 512, sigmoid_width: 100 }
+        );
+
+        let cancel = ToneMappingInfo::parse(&pack_bit_string("010 1")).unwrap();
+        assert_eq!(cancel, ToneMappingInfo { tone_map_id: 1, mapping: None });
+    }
+
+    #[test]
+    fn test_user_defined_table_and_piecewise() {
+        // 1-bit target: two 16-bit interval starts for 10-bit input
+        let bits = header("011", 10, 1) + "0000000000000000 0000001000000000";
+        let info = ToneMappingInfo::parse(&pack_bit_string(&bits)).unwrap();
+        assert_eq!(
+            info.mapping.unwrap().model,
+            ToneMapModel::UserDefinedTable { start_of_coded_interval: vec![0, 512] }
+        );
+
+        let bits = header("00100", 10, 8)
+            + "0000000000000010"
+            + "0000000001000000 00010000"
+            + "0000001110101100 11101011";
+        let info = ToneMappingInfo::parse(&pack_bit_string(&bits)).unwrap();
+        assert_eq!(
+            info.mapping.unwrap().model,
+            ToneMapModel::PiecewiseLinear {
+                coded_pivot_value: vec![64, 940],
+                target_pivot_value: vec![16, 235],
+            }
+        );
+    }
+
+    #[test]
+    fn test_luminance_dynamic_range() {
+        let bits = header("00101", 10, 8)
+            + "11111111 00000000000000000000011001000000" // Extended ISO 1600
+            + "00000101" // exposure index idc
+            + "1 0000000000000001 0000000000000011" // -1/3 stop
+            + &format!("{:032b} {:032b}", 100, 400)
+            + &format!("{:016b} {:016b} {:016b}", 64, 940, 1023);
+        let info = ToneMappingInfo::parse(&pack_bit_string(&bits)).unwrap();
+
+        let ToneMapModel::LuminanceDynamicRange(ldr) = info.mapping.unwrap().model else {
+            panic!("Expected luminance dynamic range model");
+        };
+        assert_eq!(ldr.camera_iso_speed_value, Some(1600));
+        assert_eq!(ldr.exposure_index_idc, 5);
+        assert_eq!(ldr.exposure_index_value, None);
+        assert!(ldr.exposure_compensation_value_sign_flag);
+        assert_eq!(ldr.exposure_compensation_value_denom_idc, 3);
+        assert_eq!(ldr.extended_range_white_level, 400);
+        assert_eq!(ldr.extended_white_level_code_value, 1023);
+    }
+}
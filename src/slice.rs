@@ -27,6 +27,121 @@ impl SliceType {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefPicListModification {
+    SubtractAbsDiffPicNum { abs_diff_pic_num_minus1: u32 },
+    AddAbsDiffPicNum { abs_diff_pic_num_minus1: u32 },
+    LongTermPicNum { long_term_pic_num: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PredWeight {
+    pub luma_weight_flag: bool,
+    pub luma_weight: i32,
+    pub luma_offset: i32,
+    pub chroma_weight_flag: bool,
+    pub chroma_weight: [i32; 2],
+    pub chroma_offset: [i32; 2],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PredWeightTable {
+    pub luma_log2_weight_denom: u32,
+    pub chroma_log2_weight_denom: u32,
+    pub l0: Vec<PredWeight>,
+    pub l1: Vec<PredWeight>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryManagementControlOperation {
+    MarkShortTermUnused { difference_of_pic_nums_minus1: u32 },
+    MarkLongTermUnused { long_term_pic_num: u32 },
+    AssignLongTermFrameIdx { difference_of_pic_nums_minus1: u32, long_term_frame_idx: u32 },
+    SetMaxLongTermFrameIdx { max_long_term_frame_idx_plus1: u32 },
+    MarkAllUnused,
+    MarkCurrentLongTerm { long_term_frame_idx: u32 },
+}
+
+impl MemoryManagementControlOperation {
+    pub fn as_u32(&self) -> u32 {
+        match self {
+            Self::MarkShortTermUnused { .. } => 1,
+            Self::MarkLongTermUnused { .. } => 2,
+            Self::AssignLongTermFrameIdx { .. } => 3,
+            Self::SetMaxLongTermFrameIdx { .. } => 4,
+            Self::MarkAllUnused => 5,
+            Self::MarkCurrentLongTerm { .. } => 6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecRefPicMarking {
+    pub no_output_of_prior_pics_flag: bool,
+    pub long_term_reference_flag: bool,
+    pub adaptive_ref_pic_marking_mode_flag: bool,
+    pub mmcos: Vec<MemoryManagementControlOperation>,
+}
+
+impl DecRefPicMarking {
+    pub fn parse(reader: &mut BitReader, idr_pic_flag: bool) -> Result<Self> {
+        let mut marking = DecRefPicMarking::default();
+
+        if idr_pic_flag {
+            marking.no_output_of_prior_pics_flag = reader.read_flag()?;
+            marking.long_term_reference_flag = reader.read_flag()?;
+            return Ok(marking);
+        }
+
+        marking.adaptive_ref_pic_marking_mode_flag = reader.read_flag()?;
+        if marking.adaptive_ref_pic_marking_mode_flag {
+            loop {
+                let op = match read_ue(reader)? {
+                    0 => break,
+                    1 => MemoryManagementControlOperation::MarkShortTermUnused {
+                        difference_of_pic_nums_minus1: read_ue(reader)?,
+                    },
+                    2 => MemoryManagementControlOperation::MarkLongTermUnused {
+                        long_term_pic_num: read_ue(reader)?,
+                    },
+                    3 => MemoryManagementControlOperation::AssignLongTermFrameIdx {
+                        difference_of_pic_nums_minus1: read_ue(reader)?,
+                        long_term_frame_idx: read_ue(reader)?,
+                    },
+                    4 => MemoryManagementControlOperation::SetMaxLongTermFrameIdx {
+                        max_long_term_frame_idx_plus1: read_ue(reader)?,
+                    },
+                    5 => MemoryManagementControlOperation::MarkAllUnused,
+                    6 => MemoryManagementControlOperation::MarkCurrentLongTerm {
+                        long_term_frame_idx: read_ue(reader)?,
+                    },
+                    _ => {
+                        return Err(Error::SliceParseError(
+                            "Invalid memory_management_control_operation".into(),
+                        ))
+                    }
+                };
+
+                if marking.mmcos.len() >= MAX_MMCO_COUNT {
+                    return Err(Error::SliceParseError("Too many MMCO operations".into()));
+                }
+                marking.mmcos.push(op);
+            }
+        }
+
+        Ok(marking)
+    }
+
+    pub fn has_mmco5(&self) -> bool {
+        self.mmcos
+            .iter()
+            .any(|op| matches!(op, MemoryManagementControlOperation::MarkAllUnused))
+    }
+}
+
+// Guards against runaway MMCO loops on corrupt data
+const MAX_MMCO_COUNT: usize = 66;
+
 #[derive(Debug, Clone)]
 pub struct SliceHeader {
     pub first_mb_in_slice: u32,
@@ -45,12 +160,29 @@ pub struct SliceHeader {
     pub num_ref_idx_active_override_flag: bool,
     pub num_ref_idx_l0_active_minus1: u32,
     pub num_ref_idx_l1_active_minus1: u32,
+    pub ref_pic_list_modification_flag_l0: bool,
+    pub ref_pic_list_modification_l0: Vec<RefPicListModification>,
+    pub ref_pic_list_modification_flag_l1: bool,
+    pub ref_pic_list_modification_l1: Vec<RefPicListModification>,
+    pub pred_weight_table: Option<PredWeightTable>,
+    pub dec_ref_pic_marking: Option<DecRefPicMarking>,
+    pub cabac_init_idc: u8,
+    pub slice_qp_delta: i32,
+    pub sp_for_switch_flag: bool,
+    pub slice_qs_delta: i32,
+    pub disable_deblocking_filter_idc: u8,
+    pub slice_alpha_c0_offset_div2: i8,
+    pub slice_beta_offset_div2: i8,
+    pub slice_group_change_cycle: u32,
+    /// Bit offset into the RBSP (after the NAL header byte) where slice_data() starts.
+    pub slice_data_bit_offset: usize,
 }
 
 impl SliceHeader {
     pub fn parse(
         rbsp: &[u8],
         nal_type: NalUnitType,
+        nal_ref_idc: u8,
         sps: &Sps,
         pps: &Pps,
     ) -> Result<Self> {
@@ -135,6 +267,100 @@ impl SliceHeader {
             }
         }
         
+        if num_ref_idx_l0_active_minus1 > 31 || num_ref_idx_l1_active_minus1 > 31 {
+            return Err(Error::SliceParseError("Invalid num_ref_idx_active".into()));
+        }
+        
+        let mut ref_pic_list_modification_flag_l0 = false;
+        let mut ref_pic_list_modification_l0 = Vec::new();
+        let mut ref_pic_list_modification_flag_l1 = false;
+        let mut ref_pic_list_modification_l1 = Vec::new();
+        
+        if slice_type != SliceType::I && slice_type != SliceType::Si {
+            ref_pic_list_modification_flag_l0 = reader.read_flag()?;
+            if ref_pic_list_modification_flag_l0 {
+                ref_pic_list_modification_l0 =
+                    parse_ref_pic_list_modification(&mut reader, num_ref_idx_l0_active_minus1)?;
+            }
+        }
+        
+        if slice_type == SliceType::B {
+            ref_pic_list_modification_flag_l1 = reader.read_flag()?;
+            if ref_pic_list_modification_flag_l1 {
+                ref_pic_list_modification_l1 =
+                    parse_ref_pic_list_modification(&mut reader, num_ref_idx_l1_active_minus1)?;
+            }
+        }
+        
+        let mut pred_weight_table = None;
+        if (pps.weighted_pred_flag && (slice_type == SliceType::P || slice_type == SliceType::Sp))
+            || (pps.weighted_bipred_idc == 1 && slice_type == SliceType::B)
+        {
+            pred_weight_table = Some(parse_pred_weight_table(
+                &mut reader,
+                sps,
+                slice_type,
+                num_ref_idx_l0_active_minus1,
+                num_ref_idx_l1_active_minus1,
+            )?);
+        }
+        
+        let mut dec_ref_pic_marking = None;
+        if nal_ref_idc != 0 {
+            dec_ref_pic_marking = Some(DecRefPicMarking::parse(
+                &mut reader,
+                nal_type == NalUnitType::IdrSlice,
+            )?);
+        }
+        
+        let mut cabac_init_idc = 0;
+        if pps.entropy_coding_mode_flag && slice_type != SliceType::I && slice_type != SliceType::Si {
+            cabac_init_idc = read_ue(&mut reader)?;
+            if cabac_init_idc > 2 {
+                return Err(Error::SliceParseError("Invalid cabac_init_idc".into()));
+            }
+        }
+        
+        let slice_qp_delta = read_se(&mut reader)?;
+        
+        let mut sp_for_switch_flag = false;
+        let mut slice_qs_delta = 0;
+        if slice_type == SliceType::Sp || slice_type == SliceType::Si {
+            if slice_type == SliceType::Sp {
+                sp_for_switch_flag = reader.read_flag()?;
+            }
+            slice_qs_delta = read_se(&mut reader)?;
+        }
+        
+        let mut disable_deblocking_filter_idc = 0;
+        let mut slice_alpha_c0_offset_div2 = 0;
+        let mut slice_beta_offset_div2 = 0;
+        if pps.deblocking_filter_control_present_flag {
+            disable_deblocking_filter_idc = read_ue(&mut reader)?;
+            if disable_deblocking_filter_idc > 2 {
+                return Err(Error::SliceParseError("Invalid disable_deblocking_filter_idc".into()));
+            }
+            
+            if disable_deblocking_filter_idc != 1 {
+                slice_alpha_c0_offset_div2 = read_se(&mut reader)?;
+                slice_beta_offset_div2 = read_se(&mut reader)?;
+                if !(-6..=6).contains(&slice_alpha_c0_offset_div2)
+                    || !(-6..=6).contains(&slice_beta_offset_div2)
+                {
+                    return Err(Error::SliceParseError("Invalid deblocking filter offset".into()));
+                }
+            }
+        }
+        
+        let mut slice_group_change_cycle = 0;
+        if pps.num_slice_groups_minus1 > 0 && (3..=5).contains(&pps.slice_group_map_type) {
+            let bits = slice_group_change_cycle_bits(sps, pps);
+            slice_group_change_cycle = reader.read_bits(bits)?;
+        }
+        
+        let (byte_pos, bit_pos) = reader.position();
+        let slice_data_bit_offset = byte_pos * 8 + bit_pos as usize;
+        
         Ok(SliceHeader {
             first_mb_in_slice,
             slice_type,
@@ -152,10 +378,142 @@ impl SliceHeader {
             num_ref_idx_active_override_flag,
             num_ref_idx_l0_active_minus1,
             num_ref_idx_l1_active_minus1,
+            ref_pic_list_modification_flag_l0,
+            ref_pic_list_modification_l0,
+            ref_pic_list_modification_flag_l1,
+            ref_pic_list_modification_l1,
+            pred_weight_table,
+            dec_ref_pic_marking,
+            cabac_init_idc: cabac_init_idc as u8,
+            slice_qp_delta,
+            sp_for_switch_flag,
+            slice_qs_delta,
+            disable_deblocking_filter_idc: disable_deblocking_filter_idc as u8,
+            slice_alpha_c0_offset_div2: slice_alpha_c0_offset_div2 as i8,
+            slice_beta_offset_div2: slice_beta_offset_div2 as i8,
+            slice_group_change_cycle,
+            slice_data_bit_offset,
         })
     }
 }
 
+fn parse_ref_pic_list_modification(
+    reader: &mut BitReader,
+    num_ref_idx_active_minus1: u32,
+) -> Result<Vec<RefPicListModification>> {
+    let mut modifications = Vec::new();
+    
+    loop {
+        let modification = match read_ue(reader)? {
+            0 => RefPicListModification::SubtractAbsDiffPicNum {
+                abs_diff_pic_num_minus1: read_ue(reader)?,
+            },
+            1 => RefPicListModification::AddAbsDiffPicNum {
+                abs_diff_pic_num_minus1: read_ue(reader)?,
+            },
+            2 => RefPicListModification::LongTermPicNum {
+                long_term_pic_num: read_ue(reader)?,
+            },
+            3 => break,
+            _ => {
+                return Err(Error::SliceParseError(
+                    "Invalid modification_of_pic_nums_idc".into(),
+                ))
+            }
+        };
+        
+        if modifications.len() > num_ref_idx_active_minus1 as usize {
+            return Err(Error::SliceParseError("Too many ref_pic_list modifications".into()));
+        }
+        modifications.push(modification);
+    }
+    
+    Ok(modifications)
+}
+
+fn parse_pred_weight_table(
+    reader: &mut BitReader,
+    sps: &Sps,
+    slice_type: SliceType,
+    num_ref_idx_l0_active_minus1: u32,
+    num_ref_idx_l1_active_minus1: u32,
+) -> Result<PredWeightTable> {
+    let chroma_array_type = if sps.separate_colour_plane_flag { 0 } else { sps.chroma_format_idc };
+    
+    let luma_log2_weight_denom = read_ue(reader)?;
+    if luma_log2_weight_denom > 7 {
+        return Err(Error::SliceParseError("Invalid luma_log2_weight_denom".into()));
+    }
+    
+    let mut chroma_log2_weight_denom = 0;
+    if chroma_array_type != 0 {
+        chroma_log2_weight_denom = read_ue(reader)?;
+        if chroma_log2_weight_denom > 7 {
+            return Err(Error::SliceParseError("Invalid chroma_log2_weight_denom".into()));
+        }
+    }
+    
+    let mut parse_list = |count: u32| -> Result<Vec<PredWeight>> {
+        let mut weights = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let mut weight = PredWeight {
+                luma_weight_flag: false,
+                luma_weight: 1 << luma_log2_weight_denom,
+                luma_offset: 0,
+                chroma_weight_flag: false,
+                chroma_weight: [1 << chroma_log2_weight_denom; 2],
+                chroma_offset: [0; 2],
+            };
+            
+            weight.luma_weight_flag = reader.read_flag()?;
+            if weight.luma_weight_flag {
+                weight.luma_weight = read_se(reader)?;
+                weight.luma_offset = read_se(reader)?;
+            }
+            
+            if chroma_array_type != 0 {
+                weight.chroma_weight_flag = reader.read_flag()?;
+                if weight.chroma_weight_flag {
+                    for j in 0..2 {
+                        weight.chroma_weight[j] = read_se(reader)?;
+                        weight.chroma_offset[j] = read_se(reader)?;
+                    }
+                }
+            }
+            
+            weights.push(weight);
+        }
+        Ok(weights)
+    };
+    
+    let l0 = parse_list(num_ref_idx_l0_active_minus1 + 1)?;
+    let l1 = if slice_type == SliceType::B {
+        parse_list(num_ref_idx_l1_active_minus1 + 1)?
+    } else {
+        Vec::new()
+    };
+    
+    Ok(PredWeightTable {
+        luma_log2_weight_denom,
+        chroma_log2_weight_denom,
+        l0,
+        l1,
+    })
+}
+
+// Ceil(Log2(PicSizeInMapUnits ÷ SliceGroupChangeRate + 1)) from 7.4.3
+fn slice_group_change_cycle_bits(sps: &Sps, pps: &Pps) -> u32 {
+    let pic_size_in_map_units = (sps.pic_width_in_mbs_minus1 as u64 + 1)
+        .saturating_mul(sps.pic_height_in_map_units_minus1 as u64 + 1);
+    let slice_group_change_rate = pps.slice_group_change_rate_minus1 as u64 + 1;
+    
+    let mut bits = 0;
+    while bits < 32 && (slice_group_change_rate << bits) < pic_size_in_map_units + slice_group_change_rate {
+        bits += 1;
+    }
+    bits
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PictureId {
     pub frame_num: u32,
@@ -199,9 +557,183 @@ impl PictureId {
     }
 }
 
+// P slice header with every optional field at its inferred value
+#[cfg(test)]
+pub(crate) fn test_slice_header(frame_num: u32) -> SliceHeader {
+    SliceHeader {
+        first_mb_in_slice: 0,
+        slice_type: SliceType::P,
+        pic_parameter_set_id: 0,
+        colour_plane_id: 0,
+        frame_num,
+        field_pic_flag: false,
+        bottom_field_flag: false,
+        idr_pic_id: 0,
+        pic_order_cnt_lsb: 0,
+        delta_pic_order_cnt_bottom: 0,
+        delta_pic_order_cnt: [0, 0],
+        redundant_pic_cnt: 0,
+        direct_spatial_mv_pred_flag: false,
+        num_ref_idx_active_override_flag: false,
+        num_ref_idx_l0_active_minus1: 0,
+        num_ref_idx_l1_active_minus1: 0,
+        ref_pic_list_modification_flag_l0: false,
+        ref_pic_list_modification_l0: Vec::new(),
+        ref_pic_list_modification_flag_l1: false,
+        ref_pic_list_modification_l1: Vec::new(),
+        pred_weight_table: None,
+        dec_ref_pic_marking: None,
+        cabac_init_idc: 0,
+        slice_qp_delta: 0,
+        sp_for_switch_flag: false,
+        slice_qs_delta: 0,
+        disable_deblocking_filter_idc: 0,
+        slice_alpha_c0_offset_div2: 0,
+        slice_beta_offset_div2: 0,
+        slice_group_change_cycle: 0,
+        slice_data_bit_offset: 0,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bitreader::pack_bit_string;
+    use crate::eg::{write_se, write_ue};
+
+    fn bit_string(bits: Vec<bool>) -> String {
+        bits.iter().map(|&b| if b { '1' } else { '0' }).collect()
+    }
+
+    fn ue(value: u32) -> String {
+        bit_string(write_ue(value))
+    }
+
+    fn se(value: i32) -> String {
+        bit_string(write_se(value))
+    }
+
+    fn test_sps_pps() -> (Sps, Pps) {
+        let sps = Sps { pic_order_cnt_type: 2, ..crate::sps::test_sps() };
+        let pps = Pps::parse(&[0xee, 0x3c, 0x80]).unwrap();
+        (sps, pps)
+    }
+
+    #[test]
+    fn test_full_p_slice_header() {
+        let (sps, mut pps) = test_sps_pps();
+        pps.weighted_pred_flag = true;
+
+        let mut bits = String::new();
+        bits += &ue(0); // first_mb_in_slice
+        bits += &ue(5); // slice_type P
+        bits += &ue(0); // pic_parameter_set_id
+        bits += "0101"; // frame_num = 5
+        bits.push('1'); // num_ref_idx_active_override_flag
+        bits += &ue(1); // num_ref_idx_l0_active_minus1
+        bits.push('1'); // ref_pic_list_modification_flag_l0
+        bits += &ue(0);
+        bits += &ue(2);
+        bits += &ue(2);
+        bits += &ue(7);
+        bits += &ue(3);
+        bits += &ue(5); // luma_log2_weight_denom
+        bits += &ue(4); // chroma_log2_weight_denom
+        bits.push('1');
+        bits += &se(40);
+        bits += &se(-3);
+        bits.push('0');
+        bits.push('0');
+        bits.push('1');
+        bits += &se(10);
+        bits += &se(1);
+        bits += &se(12);
+        bits += &se(-1);
+        bits.push('1'); // adaptive_ref_pic_marking_mode_flag
+        bits += &ue(1);
+        bits += &ue(0);
+        bits += &ue(6);
+        bits += &ue(2);
+        bits += &ue(0);
+        bits += &ue(1); // cabac_init_idc
+        bits += &se(-4); // slice_qp_delta
+        bits += &ue(0); // disable_deblocking_filter_idc
+        bits += &se(2);
+        bits += &se(-2);
+        let header_bits = bits.len();
+        bits.push('1');
+
+        let rbsp = pack_bit_string(&bits);
+        let header = SliceHeader::parse(&rbsp, NalUnitType::NonIdrSlice, 2, &sps, &pps).unwrap();
+
+        assert_eq!(header.slice_type, SliceType::P);
+        assert_eq!(header.frame_num, 5);
+        assert_eq!(header.num_ref_idx_l0_active_minus1, 1);
+        assert_eq!(
+            header.ref_pic_list_modification_l0,
+            vec![
+                RefPicListModification::SubtractAbsDiffPicNum { abs_diff_pic_num_minus1: 2 },
+                RefPicListModification::LongTermPicNum { long_term_pic_num: 7 },
+            ]
+        );
+
+        let pwt = header.pred_weight_table.as_ref().unwrap();
+        assert_eq!(pwt.luma_log2_weight_denom, 5);
+        assert_eq!(pwt.l0.len(), 2);
+        assert_eq!((pwt.l0[0].luma_weight, pwt.l0[0].luma_offset), (40, -3));
+        assert_eq!(pwt.l0[0].chroma_weight, [16, 16]);
+        assert_eq!(pwt.l0[1].luma_weight, 32);
+        assert_eq!(pwt.l0[1].chroma_weight, [10, 12]);
+        assert_eq!(pwt.l0[1].chroma_offset, [1, -1]);
+        assert!(pwt.l1.is_empty());
+
+        let marking = header.dec_ref_pic_marking.as_ref().unwrap();
+        assert!(marking.adaptive_ref_pic_marking_mode_flag);
+        assert_eq!(
+            marking.mmcos,
+            vec![
+                MemoryManagementControlOperation::MarkShortTermUnused { difference_of_pic_nums_minus1: 0 },
+                MemoryManagementControlOperation::MarkCurrentLongTerm { long_term_frame_idx: 2 },
+            ]
+        );
+        assert!(!marking.has_mmco5());
+
+        assert_eq!(header.cabac_init_idc, 1);
+        assert_eq!(header.slice_qp_delta, -4);
+        assert_eq!(header.disable_deblocking_filter_idc, 0);
+        assert_eq!(header.slice_alpha_c0_offset_div2, 2);
+        assert_eq!(header.slice_beta_offset_div2, -2);
+        assert_eq!(header.slice_data_bit_offset, header_bits);
+    }
+
+    #[test]
+    fn test_idr_slice_header_marking() {
+        let (sps, pps) = test_sps_pps();
+
+        let mut bits = String::new();
+        bits += &ue(0); // first_mb_in_slice
+        bits += &ue(7); // slice_type I
+        bits += &ue(0); // pic_parameter_set_id
+        bits += "0000"; // frame_num
+        bits += &ue(3); // idr_pic_id
+        bits.push('1'); // no_output_of_prior_pics_flag
+        bits.push('0'); // long_term_reference_flag
+        bits += &se(2); // slice_qp_delta
+        bits += &ue(1); // disable_deblocking_filter_idc
+        let header_bits = bits.len();
+        bits.push('1');
+
+        let rbsp = pack_bit_string(&bits);
+        let header = SliceHeader::parse(&rbsp, NalUnitType::IdrSlice, 3, &sps, &pps).unwrap();
+
+        assert_eq!(header.idr_pic_id, 3);
+        let marking = header.dec_ref_pic_marking.unwrap();
+        assert!(marking.no_output_of_prior_pics_flag);
+        assert!(!marking.long_term_reference_flag);
+        assert_eq!(header.slice_qp_delta, 2);
+        assert_eq!(header.disable_deblocking_filter_idc, 1);
+        assert_eq!(header.slice_data_bit_offset, header_bits);
+    }
 
     #[test]
     fn test_slice_type_conversion() {
@@ -1,7 +1,32 @@
 use crate::bitreader::BitReader;
 use crate::eg::{read_se, read_ue};
+use crate::vui::Vui;
 use crate::{Error, Result};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRate {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl FrameRate {
+    fn new(numerator: u64, denominator: u64) -> Option<Self> {
+        if numerator == 0 || denominator == 0 {
+            return None;
+        }
+
+        let divisor = gcd(numerator, denominator);
+        Some(FrameRate {
+            numerator: u32::try_from(numerator / divisor).ok()?,
+            denominator: u32::try_from(denominator / divisor).ok()?,
+        })
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Sps {
     pub profile_idc: u8,
@@ -28,6 +53,7 @@ pub struct Sps {
     pub offset_for_non_ref_pic: i32,
     pub offset_for_top_to_bottom_field: i32,
     pub num_ref_frames_in_pic_order_cnt_cycle: u8,
+    pub offset_for_ref_frame: Vec<i32>,
     
     pub max_num_ref_frames: u32,
     pub gaps_in_frame_num_value_allowed_flag: bool,
@@ -44,9 +70,13 @@ pub struct Sps {
     pub frame_crop_bottom_offset: u32,
     
     pub vui_parameters_present_flag: bool,
+    pub vui: Option<Vui>,
     
     pub width: u32,
     pub height: u32,
+    
+    /// The RBSP this SPS was parsed from, kept for re-muxing.
+    pub rbsp: Vec<u8>,
 }
 
 impl Sps {
@@ -117,6 +147,7 @@ impl Sps {
         let mut offset_for_non_ref_pic = 0;
         let mut offset_for_top_to_bottom_field = 0;
         let mut num_ref_frames_in_pic_order_cnt_cycle = 0;
+        let mut offset_for_ref_frame = Vec::new();
         
         match pic_order_cnt_type {
             0 => {
@@ -129,10 +160,14 @@ impl Sps {
                 delta_pic_order_always_zero_flag = reader.read_flag()?;
                 offset_for_non_ref_pic = read_se(&mut reader)?;
                 offset_for_top_to_bottom_field = read_se(&mut reader)?;
-                num_ref_frames_in_pic_order_cnt_cycle = read_ue(&mut reader)? as u8;
+                let num_ref_frames = read_ue(&mut reader)?;
+                if num_ref_frames > 255 {
+                    return Err(Error::MalformedSps("Invalid num_ref_frames_in_pic_order_cnt_cycle".into()));
+                }
+                num_ref_frames_in_pic_order_cnt_cycle = num_ref_frames as u8;
                 
                 for _ in 0..num_ref_frames_in_pic_order_cnt_cycle {
-                    let _offset_for_ref_frame = read_se(&mut reader)?;
+                    offset_for_ref_frame.push(read_se(&mut reader)?);
                 }
             }
             2 => {}
@@ -167,6 +202,11 @@ impl Sps {
         }
         
         let vui_parameters_present_flag = reader.read_flag()?;
+        let vui = if vui_parameters_present_flag {
+            Some(Vui::parse(&mut reader)?)
+        } else {
+            None
+        };
         
         let width = (pic_width_in_mbs_minus1 + 1) * 16;
         let height = (pic_height_in_map_units_minus1 + 1) * 16 * if frame_mbs_only_flag { 1 } else { 2 };
@@ -215,6 +255,7 @@ impl Sps {
             offset_for_non_ref_pic,
             offset_for_top_to_bottom_field,
             num_ref_frames_in_pic_order_cnt_cycle,
+            offset_for_ref_frame,
             max_num_ref_frames,
             gaps_in_frame_num_value_allowed_flag,
             pic_width_in_mbs_minus1,
@@ -228,10 +269,114 @@ impl Sps {
             frame_crop_top_offset,
             frame_crop_bottom_offset,
             vui_parameters_present_flag,
+            vui,
             width,
             height,
+            rbsp: rbsp.to_vec(),
         })
     }
+
+    pub fn frame_height_in_mbs(&self) -> u32 {
+        (2 - self.frame_mbs_only_flag as u32) * (self.pic_height_in_map_units_minus1 + 1)
+    }
+
+    /// MaxDpbFrames for the signalled level (A.3.1 item h), capped at 16.
+    pub fn max_dpb_frames(&self) -> u32 {
+        let frame_size_in_mbs = (self.pic_width_in_mbs_minus1 + 1).saturating_mul(self.frame_height_in_mbs());
+        let max_dpb_mbs = max_dpb_mbs(self.level_idc, self.constraint_set3_flag);
+        (max_dpb_mbs / frame_size_in_mbs.max(1)).min(16)
+    }
+
+    /// Required DPB size in frames, inferred as in E.2.1 when the VUI
+    /// bitstream restriction is absent.
+    pub fn max_dec_frame_buffering(&self) -> u32 {
+        if let Some(vui) = self.vui.as_ref().filter(|vui| vui.bitstream_restriction_flag) {
+            return vui.max_dec_frame_buffering;
+        }
+
+        if self.is_intra_profile() {
+            0
+        } else {
+            self.max_dpb_frames()
+        }
+    }
+
+    /// Largest number of frames that can precede any frame in decoding order
+    /// and follow it in output order, inferred as in E.2.1 when absent.
+    pub fn num_reorder_frames(&self) -> u32 {
+        if let Some(vui) = self.vui.as_ref().filter(|vui| vui.bitstream_restriction_flag) {
+            return vui.max_num_reorder_frames;
+        }
+
+        self.max_dec_frame_buffering()
+    }
+
+    fn is_intra_profile(&self) -> bool {
+        matches!(self.profile_idc, 44 | 86 | 100 | 110 | 122 | 244) && self.constraint_set3_flag
+    }
+
+    /// Frame rate derived from the VUI timing info (E.2.1). One clock tick is
+    /// the duration of a field, so a frame spans two ticks.
+    pub fn frame_rate(&self) -> Option<FrameRate> {
+        self.picture_rate(false, None)
+    }
+
+    /// Rate of individual pictures given the slice field_pic_flag and the
+    /// pic_struct of the picture timing SEI, if any (Table D-1 DeltaTfiDivisor).
+    pub fn picture_rate(&self, field_pic_flag: bool, pic_struct: Option<u8>) -> Option<FrameRate> {
+        let vui = self.vui.as_ref()?;
+        if !vui.timing_info_present_flag {
+            return None;
+        }
+
+        let field_pic_flag = field_pic_flag && !self.frame_mbs_only_flag;
+        let pic_struct = if vui.pic_struct_present_flag { pic_struct } else { None };
+
+        let delta_tfi_divisor = match pic_struct {
+            Some(1) | Some(2) => 1,
+            Some(0) | Some(3) | Some(4) => 2,
+            Some(5) | Some(6) => 3,
+            Some(7) => 4,
+            Some(8) => 6,
+            Some(_) => return None,
+            None if field_pic_flag => 1,
+            None => 2,
+        };
+
+        FrameRate::new(
+            vui.time_scale as u64,
+            vui.num_units_in_tick as u64 * delta_tfi_divisor,
+        )
+    }
+}
+
+// MaxDpbMbs from Table A-1
+fn max_dpb_mbs(level_idc: u8, constraint_set3_flag: bool) -> u32 {
+    match level_idc {
+        9 => 396,
+        10 => 396,
+        11 if constraint_set3_flag => 396,
+        11 => 900,
+        12 | 13 | 20 => 2376,
+        21 => 4752,
+        22 | 30 => 8100,
+        31 => 18000,
+        32 => 20480,
+        40 | 41 => 32768,
+        42 => 34816,
+        50 => 110400,
+        51 | 52 => 184320,
+        _ => 696320,
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
 }
 
 fn skip_scaling_list(reader: &mut BitReader) -> Result<()> {
@@ -249,9 +394,25 @@ fn skip_scaling_list(reader: &mut BitReader) -> Result<()> {
     Ok(())
 }
 
+// Progressive 16x16 Baseline SPS with 4-bit frame_num and pic_order_cnt_lsb
+#[cfg(test)]
+pub(crate) fn test_sps() -> Sps {
+    let ebsp = vec![
+        0x42, 0x00, 0x1f, 0xac, 0x34, 0xc8, 0x14, 0x00,
+        0x00, 0x03, 0x00, 0x04, 0x00, 0x00, 0x03, 0x00,
+        0xf0, 0x3c, 0x60, 0xc6, 0x58
+    ];
+    let mut sps = Sps::parse(&crate::nal::ebsp_to_rbsp(&ebsp)).unwrap();
+    sps.log2_max_frame_num_minus4 = 0;
+    sps.log2_max_pic_order_cnt_lsb_minus4 = 0;
+    sps.frame_mbs_only_flag = true;
+    sps
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bitreader::pack_bit_string;
     use crate::nal::ebsp_to_rbsp;
 
     #[test]
@@ -269,5 +430,38 @@ mod tests {
         assert_eq!(sps.level_idc, 31);
         assert!(sps.width > 0);
         assert!(sps.height > 0);
+        assert!(sps.frame_rate().is_none());
+    }
+
+    #[test]
+    fn test_frame_rate_from_timing_info() {
+        let mut sps = test_sps();
+
+        // Only timing_info_present_flag set: 1001 / 60000 with fixed_frame_rate_flag
+        let bits = format!("0000 1 {:032b} {:032b} 1 0000", 1001, 60000);
+        let data = pack_bit_string(&bits);
+        let vui = Vui::parse(&mut BitReader::new(&data)).unwrap();
+        sps.vui = Some(vui);
+
+        let rate = sps.frame_rate().unwrap();
+        assert_eq!((rate.numerator, rate.denominator), (30000, 1001));
+        assert!((rate.as_f64() - 29.97).abs() < 0.01);
+
+        // Field pictures are ignored for progressive-only streams
+        sps.frame_mbs_only_flag = true;
+        assert_eq!(sps.picture_rate(true, None), Some(rate));
+        sps.frame_mbs_only_flag = false;
+        assert_eq!(
+            sps.picture_rate(true, None),
+            Some(FrameRate { numerator: 60000, denominator: 1001 })
+        );
+
+        // pic_struct is only honoured when signalled in the VUI
+        assert_eq!(sps.picture_rate(false, Some(7)), Some(rate));
+        sps.vui.as_mut().unwrap().pic_struct_present_flag = true;
+        assert_eq!(
+            sps.picture_rate(false, Some(7)),
+            Some(FrameRate { numerator: 15000, denominator: 1001 })
+        );
     }
 }
\ No newline at end of file
@@ -0,0 +1,127 @@
+use crate::au::AccessUnit;
+use crate::sei::ClockTimestamp;
+use std::fmt;
+
+/// counting_type value for NTSC-style drop-frame counting (Table D-3).
+const DROP_FRAME_COUNTING_TYPE: u8 = 4;
+
+/// SMPTE ST 12-1 timecode derived from picture timing SEI clock timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timecode {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    pub drop_frame: bool,
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.drop_frame { ';' } else { ':' };
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            self.hours, self.minutes, self.seconds, separator, self.frames
+        )
+    }
+}
+
+/// Turns clock timestamps into complete timecodes. Timestamps that leave
+/// out hours, minutes or seconds take them from the previous timecode in
+/// decoding order (D.2.3); until a full timestamp has been seen such
+/// partial timestamps yield nothing.
+#[derive(Debug, Default)]
+pub struct TimecodeTracker {
+    last: Option<Timecode>,
+}
+
+impl TimecodeTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// Uses the first clock timestamp of the access unit's picture timing SEI.
+    pub fn process(&mut self, au: &AccessUnit) -> Option<Timecode> {
+        let clock_timestamp = au
+            .pic_timing()?
+            .clock_timestamps
+            .iter()
+            .flatten()
+            .next()?;
+
+        self.update(clock_timestamp)
+    }
+
+    pub fn update(&mut self, ts: &ClockTimestamp) -> Option<Timecode> {
+        let drop_frame = ts.counting_type == DROP_FRAME_COUNTING_TYPE;
+
+        let timecode = if ts.full_timestamp_flag {
+            Timecode {
+                hours: ts.hours?,
+                minutes: ts.minutes?,
+                seconds: ts.seconds?,
+                frames: ts.n_frames,
+                drop_frame,
+            }
+        } else {
+            let last = self.last?;
+            Timecode {
+                hours: ts.hours.unwrap_or(last.hours),
+                minutes: ts.minutes.unwrap_or(last.minutes),
+                seconds: ts.seconds.unwrap_or(last.seconds),
+                frames: ts.n_frames,
+                drop_frame,
+            }
+        };
+
+        self.last = Some(timecode);
+        Some(timecode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock_timestamp(n_frames: u8, seconds: Option<u8>, minutes: Option<u8>, hours: Option<u8>) -> ClockTimestamp {
+        ClockTimestamp {
+            ct_type: 0,
+            nuit_field_based_flag: false,
+            counting_type: DROP_FRAME_COUNTING_TYPE,
+            full_timestamp_flag: seconds.is_some() && minutes.is_some() && hours.is_some(),
+            discontinuity_flag: false,
+            cnt_dropped_flag: false,
+            n_frames,
+            seconds,
+            minutes,
+            hours,
+            time_offset: 0,
+        }
+    }
+
+    #[test]
+    fn test_reconstructs_partial_timestamps() {
+        let mut tracker = TimecodeTracker::new();
+
+        // Nothing to fill in from yet
+        assert_eq!(tracker.update(&clock_timestamp(3, Some(9), None, None)), None);
+
+        let full = tracker.update(&clock_timestamp(28, Some(59), Some(8), Some(10))).unwrap();
+        assert_eq!(full.to_string(), "10:08:59;28");
+
+        let frames_only = tracker.update(&clock_timestamp(29, None, None, None)).unwrap();
+        assert_eq!(frames_only.to_string(), "10:08:59;29");
+
+        // Minute 9 is not a multiple of ten, so drop-frame numbering skips
+        // frames 0 and 1; the hours are carried over
+        let rollover = tracker.update(&clock_timestamp(2, Some(0), Some(9), None)).unwrap();
+        assert_eq!(rollover.to_string(), "10:09:00;02");
+
+        tracker.reset();
+        assert_eq!(tracker.update(&clock_timestamp(3, None, None, None)), None);
+    }
+}
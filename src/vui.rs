@@ -0,0 +1,345 @@
+use crate::bitreader::BitReader;
+use crate::eg::read_ue;
+use crate::{Error, Result};
+
+pub const EXTENDED_SAR: u8 = 255;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HrdParameters {
+    pub cpb_cnt_minus1: u8,
+    pub bit_rate_scale: u8,
+    pub cpb_size_scale: u8,
+    pub bit_rate_value_minus1: Vec<u32>,
+    pub cpb_size_value_minus1: Vec<u32>,
+    pub cbr_flag: Vec<bool>,
+    pub initial_cpb_removal_delay_length_minus1: u8,
+    pub cpb_removal_delay_length_minus1: u8,
+    pub dpb_output_delay_length_minus1: u8,
+    pub time_offset_length: u8,
+}
+
+impl HrdParameters {
+    pub fn parse(reader: &mut BitReader) -> Result<Self> {
+        let cpb_cnt_minus1 = read_ue(reader)?;
+        if cpb_cnt_minus1 > 31 {
+            return Err(Error::MalformedSps("Invalid cpb_cnt_minus1".into()));
+        }
+
+        let bit_rate_scale = reader.read_bits(4)? as u8;
+        let cpb_size_scale = reader.read_bits(4)? as u8;
+
+        let cpb_cnt = cpb_cnt_minus1 as usize + 1;
+        let mut bit_rate_value_minus1 = Vec::with_capacity(cpb_cnt);
+        let mut cpb_size_value_minus1 = Vec::with_capacity(cpb_cnt);
+        let mut cbr_flag = Vec::with_capacity(cpb_cnt);
+
+        for _ in 0..cpb_cnt {
+            bit_rate_value_minus1.push(read_ue(reader)?);
+            cpb_size_value_minus1.push(read_ue(reader)?);
+            cbr_flag.push(reader.read_flag()?);
+        }
+
+        let initial_cpb_removal_delay_length_minus1 = reader.read_bits(5)? as u8;
+        let cpb_removal_delay_length_minus1 = reader.read_bits(5)? as u8;
+        let dpb_output_delay_length_minus1 = reader.read_bits(5)? as u8;
+        let time_offset_length = reader.read_bits(5)? as u8;
+
+        Ok(HrdParameters {
+            cpb_cnt_minus1: cpb_cnt_minus1 as u8,
+            bit_rate_scale,
+            cpb_size_scale,
+            bit_rate_value_minus1,
+            cpb_size_value_minus1,
+            cbr_flag,
+            initial_cpb_removal_delay_length_minus1,
+            cpb_removal_delay_length_minus1,
+            dpb_output_delay_length_minus1,
+            time_offset_length,
+        })
+    }
+
+    /// Bit rate in bits per second for the given SchedSelIdx (E.2.2).
+    pub fn bit_rate(&self, sched_sel_idx: usize) -> Option<u64> {
+        self.bit_rate_value_minus1
+            .get(sched_sel_idx)
+            .map(|v| (*v as u64 + 1) << (6 + self.bit_rate_scale))
+    }
+
+    /// CPB size in bits for the given SchedSelIdx (E.2.2).
+    pub fn cpb_size(&self, sched_sel_idx: usize) -> Option<u64> {
+        self.cpb_size_value_minus1
+            .get(sched_sel_idx)
+            .map(|v| (*v as u64 + 1) << (4 + self.cpb_size_scale))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vui {
+    pub aspect_ratio_info_present_flag: bool,
+    pub aspect_ratio_idc: u8,
+    pub sar_width: u16,
+    pub sar_height: u16,
+
+    pub overscan_info_present_flag: bool,
+    pub overscan_appropriate_flag: bool,
+
+    pub video_signal_type_present_flag: bool,
+    pub video_format: u8,
+    pub video_full_range_flag: bool,
+    pub colour_description_present_flag: bool,
+    pub colour_primaries: u8,
+    pub transfer_characteristics: u8,
+    pub matrix_coefficients: u8,
+
+    pub chroma_loc_info_present_flag: bool,
+    pub chroma_sample_loc_type_top_field: u8,
+    pub chroma_sample_loc_type_bottom_field: u8,
+
+    pub timing_info_present_flag: bool,
+    pub num_units_in_tick: u32,
+    pub time_scale: u32,
+    pub fixed_frame_rate_flag: bool,
+
+    pub nal_hrd_parameters: Option<HrdParameters>,
+    pub vcl_hrd_parameters: Option<HrdParameters>,
+    pub low_delay_hrd_flag: bool,
+    pub pic_struct_present_flag: bool,
+
+    pub bitstream_restriction_flag: bool,
+    pub motion_vectors_over_pic_boundaries_flag: bool,
+    pub max_bytes_per_pic_denom: u32,
+    pub max_bits_per_mb_denom: u32,
+    pub log2_max_mv_length_horizontal: u32,
+    pub log2_max_mv_length_vertical: u32,
+    pub max_num_reorder_frames: u32,
+    pub max_dec_frame_buffering: u32,
+}
+
+impl Vui {
+    pub fn parse(reader: &mut BitReader) -> Result<Self> {
+        let aspect_ratio_info_present_flag = reader.read_flag()?;
+        let mut aspect_ratio_idc = 0;
+        let mut sar_width = 0;
+        let mut sar_height = 0;
+
+        if aspect_ratio_info_present_flag {
+            aspect_ratio_idc = reader.read_u8()?;
+            if aspect_ratio_idc == EXTENDED_SAR {
+                sar_width = reader.read_u16()?;
+                sar_height = reader.read_u16()?;
+            }
+        }
+
+        let overscan_info_present_flag = reader.read_flag()?;
+        let mut overscan_appropriate_flag = false;
+        if overscan_info_present_flag {
+            overscan_appropriate_flag = reader.read_flag()?;
+        }
+
+        // Inferred values when absent are "unspecified" (E.2.1)
+        let video_signal_type_present_flag = reader.read_flag()?;
+        let mut video_format = 5;
+        let mut video_full_range_flag = false;
+        let mut colour_description_present_flag = false;
+        let mut colour_primaries = 2;
+        let mut transfer_characteristics = 2;
+        let mut matrix_coefficients = 2;
+
+        if video_signal_type_present_flag {
+            video_format = reader.read_bits(3)? as u8;
+            video_full_range_flag = reader.read_flag()?;
+            colour_description_present_flag = reader.read_flag()?;
+
+            if colour_description_present_flag {
+                colour_primaries = reader.read_u8()?;
+                transfer_characteristics = reader.read_u8()?;
+                matrix_coefficients = reader.read_u8()?;
+            }
+        }
+
+        let chroma_loc_info_present_flag = reader.read_flag()?;
+        let mut chroma_sample_loc_type_top_field = 0;
+        let mut chroma_sample_loc_type_bottom_field = 0;
+
+        if chroma_loc_info_present_flag {
+            chroma_sample_loc_type_top_field = read_ue(reader)?;
+            chroma_sample_loc_type_bottom_field = read_ue(reader)?;
+            if chroma_sample_loc_type_top_field > 5 || chroma_sample_loc_type_bottom_field > 5 {
+                return Err(Error::MalformedSps("Invalid chroma_sample_loc_type".into()));
+            }
+        }
+
+        let timing_info_present_flag = reader.read_flag()?;
+        let mut num_units_in_tick = 0;
+        let mut time_scale = 0;
+        let mut fixed_frame_rate_flag = false;
+
+        if timing_info_present_flag {
+            num_units_in_tick = reader.read_bits(32)?;
+            time_scale = reader.read_bits(32)?;
+            fixed_frame_rate_flag = reader.read_flag()?;
+        }
+
+        let nal_hrd_parameters_present_flag = reader.read_flag()?;
+        let nal_hrd_parameters = if nal_hrd_parameters_present_flag {
+            Some(HrdParameters::parse(reader)?)
+        } else {
+            None
+        };
+
+        let vcl_hrd_parameters_present_flag = reader.read_flag()?;
+        let vcl_hrd_parameters = if vcl_hrd_parameters_present_flag {
+            Some(HrdParameters::parse(reader)?)
+        } else {
+            None
+        };
+
+        let mut low_delay_hrd_flag = false;
+        if nal_hrd_parameters_present_flag || vcl_hrd_parameters_present_flag {
+            low_delay_hrd_flag = reader.read_flag()?;
+        }
+
+        let pic_struct_present_flag = reader.read_flag()?;
+
+        let bitstream_restriction_flag = reader.read_flag()?;
+        let mut motion_vectors_over_pic_boundaries_flag = true;
+        let mut max_bytes_per_pic_denom = 2;
+        let mut max_bits_per_mb_denom = 1;
+        let mut log2_max_mv_length_horizontal = 15;
+        let mut log2_max_mv_length_vertical = 15;
+        let mut max_num_reorder_frames = 0;
+        let mut max_dec_frame_buffering = 0;
+
+        if bitstream_restriction_flag {
+            motion_vectors_over_pic_boundaries_flag = reader.read_flag()?;
+            max_bytes_per_pic_denom = read_ue(reader)?;
+            max_bits_per_mb_denom = read_ue(reader)?;
+            log2_max_mv_length_horizontal = read_ue(reader)?;
+            log2_max_mv_length_vertical = read_ue(reader)?;
+            max_num_reorder_frames = read_ue(reader)?;
+            max_dec_frame_buffering = read_ue(reader)?;
+        }
+
+        Ok(Vui {
+            aspect_ratio_info_present_flag,
+            aspect_ratio_idc,
+            sar_width,
+            sar_height,
+            overscan_info_present_flag,
+            overscan_appropriate_flag,
+            video_signal_type_present_flag,
+            video_format,
+            video_full_range_flag,
+            colour_description_present_flag,
+            colour_primaries,
+            transfer_characteristics,
+            matrix_coefficients,
+            chroma_loc_info_present_flag,
+            chroma_sample_loc_type_top_field: chroma_sample_loc_type_top_field as u8,
+            chroma_sample_loc_type_bottom_field: chroma_sample_loc_type_bottom_field as u8,
+            timing_info_present_flag,
+            num_units_in_tick,
+            time_scale,
+            fixed_frame_rate_flag,
+            nal_hrd_parameters,
+            vcl_hrd_parameters,
+            low_delay_hrd_flag,
+            pic_struct_present_flag,
+            bitstream_restriction_flag,
+            motion_vectors_over_pic_boundaries_flag,
+            max_bytes_per_pic_denom,
+            max_bits_per_mb_denom,
+            log2_max_mv_length_horizontal,
+            log2_max_mv_length_vertical,
+            max_num_reorder_frames,
+            max_dec_frame_buffering,
+        })
+    }
+
+    /// Sample aspect ratio as (width, height), resolving the Table E-1 presets.
+    pub fn sample_aspect_ratio(&self) -> Option<(u16, u16)> {
+        if !self.aspect_ratio_info_present_flag {
+            return None;
+        }
+
+        match self.aspect_ratio_idc {
+            1 => Some((1, 1)),
+            2 => Some((12, 11)),
+            3 => Some((10, 11)),
+            4 => Some((16, 11)),
+            5 => Some((40, 33)),
+            6 => Some((24, 11)),
+            7 => Some((20, 11)),
+            8 => Some((32, 11)),
+            9 => Some((80, 33)),
+            10 => Some((18, 11)),
+            11 => Some((15, 11)),
+            12 => Some((64, 33)),
+            13 => Some((160, 99)),
+            14 => Some((4, 3)),
+            15 => Some((3, 2)),
+            16 => Some((2, 1)),
+            EXTENDED_SAR if self.sar_width != 0 && self.sar_height != 0 => {
+                Some((self.sar_width, self.sar_height))
+            }
+            _ => None,
+        }
+    }
+}
+
+// VUI with every optional section absent
+#[cfg(test)]
+pub(crate) fn test_vui() -> Vui {
+    Vui::parse(&mut BitReader::new(&[0x00, 0x00])).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitreader::pack_bit_string;
+    use crate::eg::write_ue;
+
+    fn ue(value: u32) -> String {
+        write_ue(value).iter().map(|&b| if b { '1' } else { '0' }).collect()
+    }
+
+    #[test]
+    fn test_vui_with_timing_and_hrd() {
+        let bits = [
+            format!("1 {:08b} {:016b} {:016b}", EXTENDED_SAR, 4, 3), // aspect ratio
+            "0".into(), // overscan_info_present_flag
+            "1 101 1 1 00000001 00000001 00000001".into(), // video signal type
+            "0".into(), // chroma_loc_info_present_flag
+            format!("1 {:032b} {:032b} 1", 1001, 60000), // timing info, fixed frame rate
+            format!("1 {} 0010 0011 {} {} 0", ue(0), ue(999), ue(1999)), // NAL HRD
+            "10111 10111 10111 11000".into(),
+            "0 0 1".into(), // no VCL HRD, low_delay_hrd_flag, pic_struct_present_flag
+            format!("1 1 {} {} {} {} {} {}", ue(0), ue(0), ue(16), ue(16), ue(2), ue(4)),
+            "1".into(), // rbsp_stop_one_bit
+        ]
+        .concat();
+
+        let data = pack_bit_string(&bits);
+        let mut reader = BitReader::new(&data);
+        let vui = Vui::parse(&mut reader).unwrap();
+
+        assert_eq!(vui.sample_aspect_ratio(), Some((4, 3)));
+        assert_eq!(vui.video_format, 5);
+        assert!(vui.video_full_range_flag);
+        assert_eq!(vui.colour_primaries, 1);
+        assert_eq!(vui.num_units_in_tick, 1001);
+        assert_eq!(vui.time_scale, 60000);
+        assert!(vui.fixed_frame_rate_flag);
+        assert!(vui.pic_struct_present_flag);
+
+        let hrd = vui.nal_hrd_parameters.as_ref().unwrap();
+        assert_eq!(hrd.bit_rate(0), Some(1000 << 8));
+        assert_eq!(hrd.cpb_size(0), Some(2000 << 7));
+        assert_eq!(hrd.time_offset_length, 24);
+        assert!(vui.vcl_hrd_parameters.is_none());
+
+        assert_eq!(vui.max_num_reorder_frames, 2);
+        assert_eq!(vui.max_dec_frame_buffering, 4);
+        assert!(reader.read_flag().unwrap());
+    }
+}
@@ -4,29 +4,21 @@ use h264_parser::{AnnexBParser, NalUnitType};
 fn test_parse_sps_pps_idr_sequence() {
     let mut parser = AnnexBParser::new();
     
-    // Simplified test stream with just basic NAL units
+    // Minimal SPS (POC type 2), PPS and IDR slice header
     let stream = vec![
-        // SPS (minimal valid SPS)
-        0x00, 0x00, 0x00, 0x01, 0x67, 0x42, 0x00, 0x1f,
-        0x96, 0x54, 0x0a, 0x0f, 0xff, 0x88,
-        
-        // PPS (minimal valid PPS)
-        0x00, 0x00, 0x00, 0x01, 0x68, 0xce, 0x3c, 0x80,
-        
-        // IDR slice (minimal)
-        0x00, 0x00, 0x00, 0x01, 0x65, 0x88, 0x80, 0x50,
-        0x00, 0x00, 0x03, 0x00, 0x00, 0x03, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x01, 0x67, 0x42, 0x00, 0x1e,
+        0xdd, 0xc4,
+        0x00, 0x00, 0x00, 0x01, 0x68, 0xce, 0x38, 0x80,
+        0x00, 0x00, 0x00, 0x01, 0x65, 0xb8, 0x4e,
     ];
     
     parser.push(&stream);
     
-    // Try to get NALs directly for debugging
     let mut nal_count = 0;
     let mut found_idr = false;
     
-    // The parser processes NALs and groups them into AUs
-    // For this test, just verify that parsing doesn't crash
-    while let Ok(Some(au)) = parser.next_access_unit() {
+    for au in parser.drain() {
+        let au = au.unwrap();
         nal_count += au.nals.len();
         for nal in au.nals() {
             if nal.nal_type == NalUnitType::IdrSlice {
@@ -36,9 +28,8 @@ fn test_parse_sps_pps_idr_sequence() {
         }
     }
     
-    // Basic sanity check - we processed something
-    assert!(nal_count > 0 || true, "Parser processed NAL units");
-    assert!(found_idr || true, "IDR processing verified");
+    assert_eq!(nal_count, 3);
+    assert!(found_idr);
 }
 
 #[test]
@@ -46,40 +37,46 @@ fn test_start_code_detection() {
     let mut parser = AnnexBParser::new();
     
     let stream_3byte = vec![
-        0x00, 0x00, 0x01, 0x67, 0x42, 0x00,
+        0x00, 0x00, 0x01, 0x67, 0x42, 0x00, 0x1e, 0xdd,
+        0xc4,
     ];
     
     let stream_4byte = vec![
-        0x00, 0x00, 0x00, 0x01, 0x68, 0xee,
+        0x00, 0x00, 0x00, 0x01, 0x68, 0xce, 0x38, 0x80,
+        0x00, 0x00, 0x00, 0x01, 0x65, 0xb8, 0x4e,
     ];
     
     parser.push(&stream_3byte);
     parser.push(&stream_4byte);
     
-    let mut nal_count = 0;
-    while let Ok(Some(_au)) = parser.next_access_unit() {
-        nal_count += 1;
-        if nal_count > 10 {
-            break;
-        }
-    }
-    
-    assert!(nal_count > 0 || true, "NAL units detected");
+    let start_code_lens: Vec<_> = parser
+        .drain()
+        .flat_map(|au| au.unwrap().nals)
+        .map(|nal| nal.start_code_len)
+        .collect();
+    assert_eq!(start_code_lens, [3, 4, 4]);
 }
 
 #[test]
 fn test_streaming_input() {
     let mut parser = AnnexBParser::new();
     
-    let chunk1 = vec![0x00, 0x00];
-    let chunk2 = vec![0x00, 0x01];
-    let chunk3 = vec![0x67, 0x42, 0x00, 0x1f];
+    let stream = [
+        0x00, 0x00, 0x00, 0x01, 0x67, 0x42, 0x00, 0x1e,
+        0xdd, 0xc4,
+        0x00, 0x00, 0x00, 0x01, 0x68, 0xce, 0x38, 0x80,
+        0x00, 0x00, 0x00, 0x01, 0x65, 0xb8, 0x4e,
+    ];
     
-    parser.push(&chunk1);
-    parser.push(&chunk2);
-    parser.push(&chunk3);
+    // Chunk boundaries fall inside start codes and NAL payloads
+    for chunk in stream.chunks(3) {
+        parser.push(chunk);
+    }
     
-    assert!(true, "Streaming input handled without panic");
+    let aus: Vec<_> = parser.drain().collect::<Result<_, _>>().unwrap();
+    let nal_types: Vec<_> = aus.iter().flat_map(|au| au.nals()).map(|nal| nal.nal_type).collect();
+    assert_eq!(nal_types, [NalUnitType::Sps, NalUnitType::Pps, NalUnitType::IdrSlice]);
+    assert!(aus.last().unwrap().is_keyframe());
 }
 
 #[test]
@@ -92,7 +89,7 @@ fn test_access_unit_to_bytes() {
         start_code_len: 4,
         ref_idc: 3,
         nal_type: NalUnitType::Sps,
-        ebsp: &[0x42, 0x00, 0x1f],
+        ebsp: vec![0x42, 0x00, 0x1f],
     };
     
     au.add_nal(nal);
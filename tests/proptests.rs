@@ -4,7 +4,7 @@
 use proptest::prelude::*;
 
 // Bring your crate into scope. Adjust if the crate name differs.
-use h264_parser::AnnexBParser;
+use h264_parser::{AccessUnit, AnnexBParser, AvcDecoderConfigurationRecord, AvccParser};
 
 /// ------------------------------------
 /// 2) EOF semantics / Draining behavior
@@ -24,14 +24,14 @@ fn eof_semantics_next_access_unit_is_none_after_drain() {
     assert!(matches!(parser.next_access_unit(), Ok(None)));
 }
 
-/// -------------------------------------------------------------------------
-/// 3) Structured generator: produce tiny valid SPS/PPS + (IDR|non-IDR) slice
-/// -------------------------------------------------------------------------
-/// We build a minimal, valid Annex B stream using:
-///   - POC type 2 (to avoid extra POC fields)
-///   - log2_max_frame_num_minus4 = 0  => frame_num bit width = 4
-/// Slice header contains only fields needed for AU grouping.
-/// This lets us assert is_keyframe correctness deterministically.
+// -------------------------------------------------------------------------
+// 3) Structured generator: produce tiny valid SPS/PPS + (IDR|non-IDR) slice
+// -------------------------------------------------------------------------
+// We build a minimal, valid Annex B stream using:
+//   - POC type 2 (to avoid extra POC fields)
+//   - log2_max_frame_num_minus4 = 0  => frame_num bit width = 4
+// Slice header contains only fields needed for AU grouping.
+// This lets us assert is_keyframe correctness deterministically.
 proptest! {
     #[test]
     fn keyframe_flag_matches_idr(idr in any::<bool>()) {
@@ -76,10 +76,34 @@ proptest! {
         assert_eq!(slice_aus[0].is_keyframe(), idr, "First slice AU keyframe mismatch");
 
         // The second slice AU is non-IDR
-        assert_eq!(slice_aus[1].is_keyframe(), false, "Second slice AU must be non-keyframe");
+        assert!(!slice_aus[1].is_keyframe(), "Second slice AU must be non-keyframe");
     }
 }
 
+// POC type 2 streams derive the order count from frame_num, so the
+// second reference frame lands at 2 * frame_num.
+#[test]
+fn access_units_carry_pic_order_cnt() {
+    let mut stream = Vec::new();
+    push_start_code(&mut stream); stream.extend_from_slice(&build_min_sps_pic_order_cnt_type2(0));
+    push_start_code(&mut stream); stream.extend_from_slice(&build_min_pps(0, 0));
+    push_start_code(&mut stream); stream.extend_from_slice(&build_min_slice(true, 0, 0, 0));
+    push_start_code(&mut stream); stream.extend_from_slice(&build_min_slice(false, 0, 1, 0));
+    push_start_code(&mut stream); stream.extend_from_slice(&build_min_slice(false, 0, 2, 0));
+
+    let mut parser = AnnexBParser::new();
+    parser.push(&stream);
+
+    let mut pocs = Vec::new();
+    while let Ok(Some(au)) = parser.next_access_unit() {
+        if au.first_vcl_nal().is_some() {
+            pocs.push(au.pic_order_cnt());
+        }
+    }
+
+    assert_eq!(pocs, vec![Some(0), Some(2), Some(4)]);
+}
+
 // ------------------------------------------------------
 // 4) Chunking invariants: split at arbitrary boundaries
 // ------------------------------------------------------
@@ -130,6 +154,84 @@ proptest! {
     }
 }
 
+// ------------------------------------------------------------
+// 5) Length-prefixed input: same access units as Annex B input
+// ------------------------------------------------------------
+// Parameter sets are either carried in-band or only in the avcC record.
+proptest! {
+    #[test]
+    fn avcc_matches_annexb(
+        length_size in prop::sample::select(vec![1u8, 2, 4]),
+        out_of_band in any::<bool>(),
+    ) {
+        let sps = build_min_sps_pic_order_cnt_type2(0);
+        let pps = build_min_pps(0, 0);
+        let slices = [
+            build_min_slice(true, 0, 0, 0),
+            build_min_slice(false, 0, 1, 0),
+            build_min_slice(false, 0, 2, 0),
+        ];
+
+        let mut stream = Vec::new();
+        for nal in [&sps, &pps].into_iter().chain(&slices) {
+            push_start_code(&mut stream);
+            stream.extend_from_slice(nal);
+        }
+        let mut annexb = AnnexBParser::new();
+        annexb.push(&stream);
+        let mut expected = vcl_access_units(|| annexb.next_access_unit().unwrap());
+
+        prop_assert_eq!(expected.len(), 3);
+        if out_of_band {
+            // The in-band parameter sets belong to the IDR access unit
+            expected[0].2.retain(|(nal_type, _)| !matches!(nal_type, 7 | 8));
+        }
+
+        let mut avcc = if out_of_band {
+            let mut record = vec![0x01, 0x42, 0xc0, 0x0a, 0xfc | (length_size - 1), 0xe1];
+            record.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+            record.extend_from_slice(&sps);
+            record.push(1);
+            record.extend_from_slice(&(pps.len() as u16).to_be_bytes());
+            record.extend_from_slice(&pps);
+            let config = AvcDecoderConfigurationRecord::parse(&record).unwrap();
+            AvccParser::from_config(&config).unwrap()
+        } else {
+            let mut parser = AvccParser::new(length_size).unwrap();
+            parser.push_sample(&length_prefixed(&[&sps, &pps], length_size)).unwrap();
+            parser
+        };
+        for slice in &slices {
+            avcc.push_sample(&length_prefixed(&[slice], length_size)).unwrap();
+        }
+        let actual = vcl_access_units(|| avcc.next_access_unit().unwrap());
+
+        prop_assert_eq!(actual, expected);
+    }
+}
+
+fn length_prefixed(nals: &[&Vec<u8>], length_size: u8) -> Vec<u8> {
+    let mut sample = Vec::new();
+    for nal in nals {
+        let len = (nal.len() as u32).to_be_bytes();
+        sample.extend_from_slice(&len[4 - length_size as usize..]);
+        sample.extend_from_slice(nal);
+    }
+    sample
+}
+
+type AuSummary = (bool, Option<i32>, Vec<(u8, Vec<u8>)>);
+
+fn vcl_access_units(next: impl FnMut() -> Option<AccessUnit>) -> Vec<AuSummary> {
+    std::iter::from_fn(next)
+        .filter(|au| au.first_vcl_nal().is_some())
+        .map(|au| {
+            let nals = au.nals().map(|n| (n.nal_type.as_u8(), n.ebsp.clone())).collect();
+            (au.is_keyframe(), au.pic_order_cnt(), nals)
+        })
+        .collect()
+}
+
 /* -----------------------------
    Helpers: minimal bit/UE writer
    ----------------------------- */
@@ -219,14 +321,18 @@ fn build_min_slice(idr: bool, pps_id: u32, frame_num: u32, idr_pic_id: u32) -> V
         ue(&mut rbsp, idr_pic_id);   // idr_pic_id (only for IDR)
     }
     // POC type 2 => no POC fields
-    
-    // Add minimal slice data to make it a valid slice
-    // For simplicity, we'll add some dummy bits to make the slice look valid
-    // These represent the simplest macroblock data
     if !idr {
-        // For P slices, add num_ref_idx_active_override_flag
-        rbsp.write_flag(false);
+        rbsp.write_flag(false);      // num_ref_idx_active_override_flag
+        rbsp.write_flag(false);      // ref_pic_list_modification_flag_l0
+    }
+    // dec_ref_pic_marking (nal_ref_idc = 3)
+    if idr {
+        rbsp.write_flag(false);      // no_output_of_prior_pics_flag
+        rbsp.write_flag(false);      // long_term_reference_flag
+    } else {
+        rbsp.write_flag(false);      // adaptive_ref_pic_marking_mode_flag
     }
+    se(&mut rbsp, 0);                // slice_qp_delta
     
     // Add a simple macroblock (mb_skip_run for P, or I macroblock for I)
     if idr {